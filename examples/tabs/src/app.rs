@@ -1,6 +1,7 @@
 use egui_term::{PtyEvent, TerminalBackend, TerminalView};
 use std::{
     collections::BTreeMap,
+    path::PathBuf,
     sync::mpsc::{self, Receiver, Sender},
 };
 
@@ -33,7 +34,11 @@ impl eframe::App for App {
                     self.tab_manager.remove(tab_id);
                 },
                 egui_term::PtyEvent::Title(title) => {
-                    self.tab_manager.set_title(tab_id, title);
+                    self.tab_manager.set_title(tab_id, Some(title));
+                },
+                // Sent when a popped title stack entry had no title.
+                egui_term::PtyEvent::ResetTitle => {
+                    self.tab_manager.set_title(tab_id, None);
                 },
                 _ => {},
             }
@@ -92,7 +97,11 @@ impl TabManager {
         ctx: egui::Context,
     ) {
         let id = self.tabs.len() as u64;
-        let tab = Tab::new(ctx, command_sender, id);
+        // Open the new tab in the folder of the active one
+        let working_directory = self
+            .get_active()
+            .and_then(|tab| tab.backend.current_directory());
+        let tab = Tab::new(ctx, command_sender, id, working_directory);
         self.tabs.insert(id, tab);
         self.active_tab_id = Some(id)
     }
@@ -115,14 +124,17 @@ impl TabManager {
         self.tabs.clear();
     }
 
-    fn set_title(&mut self, id: u64, title: String) {
+    fn set_title(&mut self, id: u64, title: Option<String>) {
         if let Some(tab) = self.tabs.get_mut(&id) {
             tab.set_title(title);
         }
     }
 
     fn get_title(&mut self, id: u64) -> Option<String> {
-        self.tabs.get(&id).map(|tab| tab.title.clone())
+        let tab = self.tabs.get(&id)?;
+        tab.title
+            .clone()
+            .or_else(|| tab.backend.foreground_process_name())
     }
 
     fn get_active(&mut self) -> Option<&mut Tab> {
@@ -150,7 +162,7 @@ impl TabManager {
 
 struct Tab {
     backend: TerminalBackend,
-    title: String,
+    title: Option<String>,
 }
 
 impl Tab {
@@ -158,6 +170,7 @@ impl Tab {
         ctx: egui::Context,
         command_sender: Sender<(u64, PtyEvent)>,
         id: u64,
+        working_directory: Option<PathBuf>,
     ) -> Self {
         let system_shell = std::env::var("SHELL")
             .expect("SHELL variable is not defined")
@@ -169,17 +182,19 @@ impl Tab {
             command_sender,
             egui_term::BackendSettings {
                 shell: system_shell,
+                working_directory,
+                ..Default::default()
             },
         )
         .unwrap();
 
         Self {
             backend,
-            title: format!("tab: {}", id),
+            title: None,
         }
     }
 
-    fn set_title(&mut self, title: String) {
+    fn set_title(&mut self, title: Option<String>) {
         self.title = title;
     }
 }
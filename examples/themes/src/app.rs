@@ -23,6 +23,7 @@ impl App {
             pty_proxy_sender.clone(),
             egui_term::BackendSettings {
                 shell: system_shell,
+                ..Default::default()
             },
         )
         .unwrap();
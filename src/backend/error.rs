@@ -0,0 +1,53 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum BackendError {
+    /// The configured shell does not exist or is not on `PATH`.
+    ShellNotFound(String),
+    /// Opening the pty or spawning the shell in it failed.
+    PtyAllocation(io::Error),
+    /// A url or search regex could not be compiled.
+    RegexCompile(String),
+    /// The event subscription thread could not be started.
+    ThreadSpawn(io::Error),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShellNotFound(shell) => {
+                write!(f, "shell {} not found", shell)
+            },
+            Self::PtyAllocation(err) => {
+                write!(f, "pty allocation failed: {}", err)
+            },
+            Self::RegexCompile(err) => {
+                write!(f, "regex compilation failed: {}", err)
+            },
+            Self::ThreadSpawn(err) => write!(f, "thread spawn failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::PtyAllocation(err) | Self::ThreadSpawn(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<BackendError> for io::Error {
+    fn from(err: BackendError) -> Self {
+        match err {
+            BackendError::ShellNotFound(_) => {
+                io::Error::new(io::ErrorKind::NotFound, err)
+            },
+            BackendError::PtyAllocation(err)
+            | BackendError::ThreadSpawn(err) => err,
+            BackendError::RegexCompile(_) => io::Error::other(err),
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::Line;
+use alacritty_terminal::sync::FairMutex;
+use alacritty_terminal::term::{Term, TermMode};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Longest OSC sequence kept while scanning, enough for the path of an
+/// OSC 7 sequence. The parameters of longer ones are not ours to read.
+const MAX_OSC_LEN: usize = 4200;
+
+/// Identifies a mark added with `TerminalBackend::add_mark`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MarkId(u64);
+
+/// Marked grid lines. Lines move up as output scrolls into the history, so
+/// every mark keeps the [`ScrollCounter`] count it was added at and is
+/// shifted by the lines scrolled since.
+#[derive(Debug, Default)]
+pub(crate) struct Marks {
+    marks: Vec<Mark>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct Mark {
+    id: MarkId,
+    line: Line,
+    scrolled: i64,
+}
+
+impl Marks {
+    pub fn add(&mut self, line: Line, scrolled: i64) -> MarkId {
+        let id = MarkId(self.next_id);
+        self.next_id += 1;
+        self.marks.push(Mark { id, line, scrolled });
+        id
+    }
+
+    pub fn remove(&mut self, id: MarkId) {
+        self.marks.retain(|mark| mark.id != id);
+    }
+
+    pub fn clear(&mut self) {
+        self.marks.clear();
+    }
+
+    /// Current lines of the marks still in the grid, oldest first.
+    pub fn lines(
+        &self,
+        scrolled: i64,
+        history_size: usize,
+    ) -> Vec<(MarkId, Line)> {
+        self.marks
+            .iter()
+            .filter_map(|mark| {
+                Some((mark.id, mark.current_line(scrolled, history_size)?))
+            })
+            .collect()
+    }
+
+    pub fn line(
+        &self,
+        id: MarkId,
+        scrolled: i64,
+        history_size: usize,
+    ) -> Option<Line> {
+        self.marks
+            .iter()
+            .find(|mark| mark.id == id)?
+            .current_line(scrolled, history_size)
+    }
+}
+
+impl Mark {
+    fn current_line(&self, scrolled: i64, history_size: usize) -> Option<Line> {
+        shifted_line(self.line, self.scrolled, scrolled, history_size)
+    }
+}
+
+/// Moves `line`, recorded when `then` lines had scrolled, up by the lines
+/// scrolled since. None once it left the scrollback.
+fn shifted_line(
+    line: Line,
+    then: i64,
+    scrolled: i64,
+    history_size: usize,
+) -> Option<Line> {
+    let line = i64::from(line.0) - (scrolled - then);
+    (line >= -(history_size as i64)).then_some(Line(line as i32))
+}
+
+/// Lines the history may grow past its size between two updates of the
+/// [`ScrollCounter`].
+const SCROLL_HEADROOM: usize = 4096;
+
+/// Counts the lines scrolled into the history, also once the scrollback is
+/// full. A full history no longer grows, so the counter lets it grow by
+/// [`SCROLL_HEADROOM`] lines, counts the growth and trims it back to its
+/// size on every update.
+#[derive(Debug)]
+pub(crate) struct ScrollCounter {
+    scrolled: i64,
+    history_size: usize,
+    max_history_size: usize,
+}
+
+pub(crate) type SharedScrollCounter = Arc<Mutex<ScrollCounter>>;
+
+impl ScrollCounter {
+    pub fn new(max_history_size: usize) -> Self {
+        Self {
+            scrolled: 0,
+            history_size: 0,
+            max_history_size,
+        }
+    }
+
+    /// Lines scrolled since the terminal started, counted up to `term`.
+    /// More lines than [`SCROLL_HEADROOM`] between two updates are counted
+    /// as that many.
+    pub fn update<T>(&mut self, term: &mut Term<T>) -> i64 {
+        // The alternate screen has a grid of its own, the count follows the
+        // main screen.
+        if term.mode().contains(TermMode::ALT_SCREEN) {
+            return self.scrolled;
+        }
+
+        let grid = term.grid_mut();
+        // Clearing the history shrinks it without scrolling.
+        self.scrolled +=
+            grid.history_size().saturating_sub(self.history_size) as i64;
+        grid.update_history(self.max_history_size);
+        grid.update_history(self.max_history_size + SCROLL_HEADROOM);
+        self.history_size = grid.history_size();
+        self.scrolled
+    }
+}
+
+/// Boundary of a shell command, reported by shells with OSC 133 shell
+/// integration ("semantic prompts").
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptMarkKind {
+    /// `OSC 133 ; A`, the prompt is about to be printed.
+    PromptStart,
+    /// `OSC 133 ; B`, the prompt ended and the user types the command.
+    CommandStart,
+    /// `OSC 133 ; C`, the command runs and its output follows.
+    OutputStart,
+    /// `OSC 133 ; D`, the command finished, usually with its exit code.
+    CommandFinished { exit_code: Option<i32> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PromptMark {
+    pub kind: PromptMarkKind,
+    /// Line in grid coordinates.
+    pub line: Line,
+}
+
+/// Shell integration marks, filled from the PTY reader thread.
+#[derive(Debug, Default)]
+pub(crate) struct PromptMarks {
+    marks: Vec<(PromptMarkKind, Line, i64)>,
+}
+
+pub(crate) type SharedPromptMarks = Arc<Mutex<PromptMarks>>;
+
+impl PromptMarks {
+    pub fn push(&mut self, kind: PromptMarkKind, line: Line, scrolled: i64) {
+        self.marks.push((kind, line, scrolled));
+    }
+
+    /// Current lines of the marks, oldest first. Marks that left the
+    /// scrollback are dropped.
+    pub fn lines(
+        &mut self,
+        scrolled: i64,
+        history_size: usize,
+    ) -> Vec<PromptMark> {
+        let mut lines = Vec::with_capacity(self.marks.len());
+        self.marks.retain(|(kind, line, then)| {
+            let Some(line) = shifted_line(*line, *then, scrolled, history_size)
+            else {
+                return false;
+            };
+            lines.push(PromptMark { kind: *kind, line });
+            true
+        });
+        lines
+    }
+}
+
+/// Shell integration found in the PTY output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ShellOsc {
+    /// `OSC 133`, a prompt mark.
+    PromptMark(PromptMarkKind),
+    /// `OSC 7 ; file://host/path`, the working directory of the shell.
+    Directory(PathBuf),
+}
+
+/// Working directory last reported with OSC 7.
+pub(crate) type SharedDirectory = Arc<Mutex<Option<PathBuf>>>;
+
+/// Finds OSC 133 and OSC 7 sequences in a byte stream, also when they are
+/// split across reads.
+#[derive(Debug, Default)]
+pub(crate) struct OscParser {
+    state: ScanState,
+    osc: Vec<u8>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+enum ScanState {
+    #[default]
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+impl OscParser {
+    pub fn advance(&mut self, byte: u8) -> Option<ShellOsc> {
+        match (&self.state, byte) {
+            (ScanState::Ground, 0x1b) => self.state = ScanState::Escape,
+            (ScanState::Ground, _) => {},
+            (ScanState::Escape, b']') => {
+                self.osc.clear();
+                self.state = ScanState::Osc;
+            },
+            (ScanState::Escape, 0x1b) => {},
+            (ScanState::Escape, _) => self.state = ScanState::Ground,
+            // BEL or ST terminate the sequence.
+            (ScanState::Osc, 0x07) | (ScanState::OscEscape, b'\\') => {
+                self.state = ScanState::Ground;
+                return parse_osc(&self.osc);
+            },
+            (ScanState::Osc, 0x1b) => self.state = ScanState::OscEscape,
+            (ScanState::Osc, _) if self.osc.len() < MAX_OSC_LEN => {
+                self.osc.push(byte);
+            },
+            (ScanState::Osc, _) => {},
+            (ScanState::OscEscape, b']') => {
+                self.osc.clear();
+                self.state = ScanState::Osc;
+            },
+            (ScanState::OscEscape, _) => self.state = ScanState::Ground,
+        }
+        None
+    }
+}
+
+fn parse_osc(osc: &[u8]) -> Option<ShellOsc> {
+    let (number, params) = osc.split_at(osc.iter().position(|b| *b == b';')?);
+    match number {
+        b"133" => parse_osc_133(&params[1..]).map(ShellOsc::PromptMark),
+        b"7" => parse_osc_7(&params[1..]).map(ShellOsc::Directory),
+        _ => None,
+    }
+}
+
+fn parse_osc_133(params: &[u8]) -> Option<PromptMarkKind> {
+    let mut params = params.split(|byte| *byte == b';');
+    match params.next()? {
+        b"A" => Some(PromptMarkKind::PromptStart),
+        b"B" => Some(PromptMarkKind::CommandStart),
+        b"C" => Some(PromptMarkKind::OutputStart),
+        b"D" => Some(PromptMarkKind::CommandFinished {
+            exit_code: params
+                .next()
+                .and_then(|code| std::str::from_utf8(code).ok())
+                .and_then(|code| code.parse().ok()),
+        }),
+        _ => None,
+    }
+}
+
+/// Path of a `file://host/path` URL, percent-decoded. The host is left to
+/// the caller, who can check that the path exists.
+fn parse_osc_7(url: &[u8]) -> Option<PathBuf> {
+    let url = url.strip_prefix(b"file://")?;
+    let path = &url[url.iter().position(|byte| *byte == b'/')?..];
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.iter();
+    while let Some(byte) = bytes.next() {
+        if *byte != b'%' {
+            decoded.push(*byte);
+            continue;
+        }
+
+        let hex = [*bytes.next()?, *bytes.next()?];
+        decoded.push(
+            u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?,
+        );
+    }
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+/// Records the OSC 7 directory and the OSC 133 marks of the bytes read from
+/// the PTY. The bytes are
+/// not parsed into the grid yet, so the line of a mark is the line of the
+/// cursor when the term was last free, moved down by the line feeds read
+/// since. Soft wraps and cursor movement in between make it approximate.
+pub(crate) struct PromptMarkRecorder<T> {
+    term: Arc<FairMutex<Term<T>>>,
+    marks: SharedPromptMarks,
+    directory: SharedDirectory,
+    scroll_counter: SharedScrollCounter,
+    parser: OscParser,
+    line_feeds: usize,
+    anchor: (Line, i64, usize),
+}
+
+impl<T> PromptMarkRecorder<T> {
+    pub fn new(
+        term: Arc<FairMutex<Term<T>>>,
+        marks: SharedPromptMarks,
+        directory: SharedDirectory,
+        scroll_counter: SharedScrollCounter,
+    ) -> Self {
+        Self {
+            term,
+            marks,
+            directory,
+            scroll_counter,
+            parser: OscParser::default(),
+            line_feeds: 0,
+            anchor: (Line(0), 0, 0),
+        }
+    }
+
+    pub fn scan(&mut self, bytes: &[u8]) {
+        // The event loop holds the lock while it parses, so this fails
+        // instead of blocking its own thread.
+        if let Some(mut term) = self.term.try_lock_unfair() {
+            if let Ok(mut scroll_counter) = self.scroll_counter.lock() {
+                let scrolled = scroll_counter.update(&mut *term);
+                self.anchor =
+                    (term.grid().cursor.point.line, scrolled, self.line_feeds);
+            }
+        }
+
+        for byte in bytes {
+            if *byte == b'\n' {
+                self.line_feeds += 1;
+            }
+            match self.parser.advance(*byte) {
+                Some(ShellOsc::PromptMark(kind)) => {
+                    let (line, scrolled, line_feeds) = self.anchor;
+                    let line = line + (self.line_feeds - line_feeds) as i32;
+                    if let Ok(mut marks) = self.marks.lock() {
+                        marks.push(kind, line, scrolled);
+                    }
+                },
+                Some(ShellOsc::Directory(path)) => {
+                    if let Ok(mut directory) = self.directory.lock() {
+                        *directory = Some(path);
+                    }
+                },
+                None => {},
+            }
+        }
+    }
+}
@@ -1,29 +1,60 @@
+pub mod error;
+pub mod marks;
+pub mod pty;
 pub mod settings;
+pub mod state;
 
-use crate::types::Size;
+use crate::bindings::{
+    BindingAction, BindingsLayout, InputKind, SELECTION_MODE,
+};
+use crate::types::{CellGeometry, Size};
 use alacritty_terminal::event::{
     Event, EventListener, Notify, OnResize, WindowSize,
 };
 use alacritty_terminal::event_loop::{EventLoop, Msg, Notifier};
-use alacritty_terminal::grid::{Dimensions, Scroll};
-use alacritty_terminal::index::{Column, Direction, Line, Point, Side};
+use alacritty_terminal::grid::{Dimensions, Indexed, Scroll};
+use alacritty_terminal::index::{
+    Boundary, Column, Direction, Line, Point, Side,
+};
 use alacritty_terminal::selection::{
     Selection, SelectionRange, SelectionType as AlacrittySelectionType,
 };
 use alacritty_terminal::sync::FairMutex;
 use alacritty_terminal::term::search::{Match, RegexIter, RegexSearch};
 use alacritty_terminal::term::{
-    self, cell::Cell, test::TermSize, viewport_to_point, Term, TermMode,
+    self, cell, cell::Cell, test::TermSize, viewport_to_point, Term, TermMode,
 };
-use alacritty_terminal::{tty, Grid};
-use egui::Modifiers;
-use settings::BackendSettings;
+use alacritty_terminal::tty::{self, EventedPty};
+use alacritty_terminal::vi_mode::ViMotion;
+use alacritty_terminal::vte::ansi;
+use alacritty_terminal::Grid;
+use egui::{Key, Modifiers, Pos2, Rect, Vec2};
+use error::BackendError;
+use marks::{
+    MarkId, Marks, OscParser, PromptMark, PromptMarkKind, PromptMarkRecorder,
+    ScrollCounter, SharedDirectory, SharedPromptMarks, SharedScrollCounter,
+    ShellOsc,
+};
+#[cfg(unix)]
+use pty::FdPty;
+use pty::{AltScreenFilter, PtyTap, SharedPtyTap, TappedPty};
+use settings::{BackendSettings, ScrollOnOutput};
+use state::TerminalStateBlob;
 use std::borrow::Cow;
 use std::cmp::min;
+use std::fmt::Write;
 use std::io::Result;
-use std::ops::{Index, RangeInclusive};
+use std::ops::{Deref, RangeInclusive};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(not(feature = "tokio"))]
+use std::sync::mpsc;
 use std::sync::mpsc::Sender;
-use std::sync::{mpsc, Arc};
+use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use std::time::Duration;
 
 pub type TerminalMode = TermMode;
 pub type PtyEvent = Event;
@@ -32,12 +63,26 @@ pub type SelectionType = AlacrittySelectionType;
 #[derive(Debug, Clone)]
 pub enum BackendCommand {
     Write(Vec<u8>),
+    /// Like `Write`, but keeps the scroll position, e.g. for markers sent
+    /// while the user reads the scrollback.
+    WriteNoScroll(Vec<u8>),
+    Paste(String),
     Scroll(i32),
     Resize(Size, Size),
     SelectStart(SelectionType, f32, f32),
     SelectUpdate(f32, f32),
     ProcessLink(LinkAction, Point),
     MouseReport(MouseButton, Modifiers, Point, bool),
+    ClearSelection,
+    SelectAll,
+    /// Enters or leaves copy mode, where the keyboard moves a cursor over
+    /// the grid and selects text instead of typing, e.g. while the program
+    /// captures the mouse.
+    ToggleCopyMode,
+    CopyModeMotion(CopyModeMotion),
+    /// Starts a selection at the copy mode cursor, or drops the current
+    /// one. Motions then move its end.
+    CopyModeSelect,
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +117,72 @@ pub enum MouseButton {
     Other = 99,
 }
 
+/// Part of the active screen that [`TerminalBackend::search`] looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchScope {
+    /// Only the lines currently shown in the viewport.
+    Visible,
+    /// The whole scrollback and the screen. The alternate screen has no
+    /// scrollback, so there it is the same as `Visible`.
+    History,
+}
+
+/// Grid size passed to the callback set with
+/// [`TerminalBackend::set_resize_callback`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridSize {
+    pub cols: u16,
+    pub rows: u16,
+    /// Size of the area the grid was laid out in, in points.
+    pub size: Vec2,
+}
+
+pub type ResizeCallback = Box<dyn FnMut(GridSize) + Send>;
+
+/// Moves of the copy mode cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CopyModeMotion {
+    Up,
+    Down,
+    Left,
+    Right,
+    LineStart,
+    LineEnd,
+    WordLeft,
+    WordRight,
+}
+
+impl From<CopyModeMotion> for ViMotion {
+    fn from(motion: CopyModeMotion) -> Self {
+        match motion {
+            CopyModeMotion::Up => ViMotion::Up,
+            CopyModeMotion::Down => ViMotion::Down,
+            CopyModeMotion::Left => ViMotion::Left,
+            CopyModeMotion::Right => ViMotion::Right,
+            CopyModeMotion::LineStart => ViMotion::First,
+            CopyModeMotion::LineEnd => ViMotion::Last,
+            CopyModeMotion::WordLeft => ViMotion::WordLeft,
+            CopyModeMotion::WordRight => ViMotion::WordRight,
+        }
+    }
+}
+
+/// A key press that [`TerminalBackend::send_key`] leaves to the app,
+/// because it needs the clipboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardRequest {
+    /// Text of the selection to put on the clipboard.
+    Copy(String),
+    /// Clipboard text to send with [`TerminalBackend::paste`].
+    Paste,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum KeyAction {
+    Command(BackendCommand),
+    Clipboard(ClipboardRequest),
+}
+
 #[derive(Debug, Clone)]
 pub enum LinkAction {
     Clear,
@@ -138,67 +249,535 @@ pub struct TerminalBackend {
     pub url_regex: RegexSearch,
     term: Arc<FairMutex<Term<EventProxy>>>,
     size: TerminalSize,
-    notifier: Notifier,
+    notifier: Option<Notifier>,
+    child_pid: Option<u32>,
+    parser: ansi::Processor,
     last_content: RenderableContent,
+    generation: Arc<AtomicU64>,
+    synced_generation: u64,
+    exited: Arc<AtomicBool>,
+    keep_open_on_exit: bool,
+    has_output: Arc<AtomicBool>,
+    title: SharedTitle,
+    app_context: egui::Context,
+    pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
+    settings: Option<BackendSettings>,
+    input_tap: SharedPtyTap,
+    output_tap: SharedPtyTap,
+    backspace_sends_delete: bool,
+    confirm_multiline_paste: bool,
+    scroll_on_output: ScrollOnOutput,
+    /// Lines scrolled at the last sync and whether the view was at the
+    /// bottom, kept while an unfocused view holds its viewport.
+    held_viewport: Option<(i64, bool)>,
+    pending_paste: Option<String>,
+    marks: Marks,
+    resize_callback: Option<ResizeCallback>,
+    prompt_marks: SharedPromptMarks,
+    scroll_counter: SharedScrollCounter,
+    osc_parser: OscParser,
+    directory: SharedDirectory,
+    #[cfg(unix)]
+    master_fd: Option<RawFd>,
 }
 
 impl TerminalBackend {
+    /// Spawns the shell of `settings` in a new PTY.
+    ///
+    /// With the `tokio` feature the PTY events are forwarded by a task on
+    /// the current Tokio runtime, so this and the other constructors must
+    /// be called from within a runtime, otherwise they fail.
     pub fn new(
         id: u64,
         app_context: egui::Context,
         pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
         settings: BackendSettings,
     ) -> Result<Self> {
+        Ok(Self::try_new(
+            id,
+            app_context,
+            pty_event_proxy_sender,
+            settings,
+        )?)
+    }
+
+    /// Like [`TerminalBackend::new`], but reports which step failed, so
+    /// the application can show e.g. "shell /bin/zsh not found".
+    pub fn try_new(
+        id: u64,
+        app_context: egui::Context,
+        pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
+        settings: BackendSettings,
+    ) -> std::result::Result<Self, BackendError> {
+        if !shell_exists(&settings.shell) {
+            return Err(BackendError::ShellNotFound(settings.shell));
+        }
+
+        let original_settings = settings.clone();
+        let backspace_sends_delete = settings.backspace_sends_delete;
+        let confirm_multiline_paste = settings.confirm_multiline_paste;
+        let scroll_on_output = settings.scroll_on_output;
         let pty_config = tty::Options {
             shell: Some(tty::Shell::new(settings.shell, vec![])),
+            working_directory: settings.working_directory,
             ..tty::Options::default()
         };
-        let config = term::Config::default();
-        let terminal_size = TerminalSize::default();
-        let pty = tty::new(&pty_config, terminal_size.into(), id)?;
-        let (event_sender, event_receiver) = mpsc::channel();
-        let event_proxy = EventProxy(event_sender);
-        let mut term = Term::new(config, &terminal_size, event_proxy.clone());
-        let initial_content = RenderableContent {
-            grid: term.grid().clone(),
-            selectable_range: None,
-            terminal_mode: *term.mode(),
-            terminal_size,
-            cursor: term.grid_mut().cursor_cell().clone(),
-            hovered_hyperlink: None,
+        let terminal_size = match settings.initial_grid_size {
+            Some((cols, lines)) if cols > 0 && lines > 0 => TerminalSize {
+                num_cols: cols,
+                num_lines: lines,
+                ..TerminalSize::default()
+            },
+            _ => TerminalSize::default(),
         };
+        let pty = tty::new(&pty_config, terminal_size.into(), id)
+            .map_err(BackendError::PtyAllocation)?;
+        #[cfg(unix)]
+        let child_pid = Some(pty.child().id());
+        #[cfg(not(unix))]
+        let child_pid = None;
+        #[cfg(unix)]
+        let master_fd = pty.file().as_raw_fd();
+
+        let mut backend = Self::with_pty(
+            id,
+            app_context,
+            pty_event_proxy_sender,
+            pty,
+            child_pid,
+            terminal_size,
+            term::Config {
+                scrolling_history: original_settings.scrollback_lines,
+                ..term::Config::default()
+            },
+            original_settings.allow_alt_screen,
+            SubscriptionOptions::from(&original_settings),
+        )?;
+        backend.backspace_sends_delete = backspace_sends_delete;
+        backend.confirm_multiline_paste = confirm_multiline_paste;
+        backend.scroll_on_output = scroll_on_output;
+        backend.settings = Some(original_settings);
+        #[cfg(unix)]
+        {
+            backend.master_fd = Some(master_fd);
+        }
+        Ok(backend)
+    }
+
+    /// Creates a backend around a pty opened by the application, e.g. an
+    /// ssh or container exec session. No process is spawned, the bytes
+    /// written to `master` are rendered and the input is sent back to it.
+    /// `slave` is closed, so pass it once the program running in the pty
+    /// has its own handle: when that program closes it, the pty hangs up
+    /// and [`PtyEvent::Exit`] is sent. Needs a Tokio runtime like
+    /// [`TerminalBackend::new`].
+    #[cfg(unix)]
+    pub fn from_raw_fd(
+        id: u64,
+        app_context: egui::Context,
+        pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
+        master: OwnedFd,
+        slave: OwnedFd,
+    ) -> Result<Self> {
+        let master_fd = master.as_raw_fd();
+        let pty = FdPty::new(master, slave)?;
+        let mut backend = Self::with_pty(
+            id,
+            app_context,
+            pty_event_proxy_sender,
+            pty,
+            None,
+            TerminalSize::default(),
+            term::Config::default(),
+            true,
+            SubscriptionOptions::default(),
+        )?;
+        backend.master_fd = Some(master_fd);
+        Ok(backend)
+    }
+
+    fn with_pty<P>(
+        id: u64,
+        app_context: egui::Context,
+        pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
+        pty: P,
+        child_pid: Option<u32>,
+        terminal_size: TerminalSize,
+        config: term::Config,
+        allow_alt_screen: bool,
+        options: SubscriptionOptions,
+    ) -> std::result::Result<Self, BackendError>
+    where
+        P: EventedPty + OnResize + Send + 'static,
+    {
+        let url_regex = default_url_regex()?;
+        let (event_sender, event_receiver) = event_channel();
+        let generation = Arc::new(AtomicU64::new(0));
+        let exited = Arc::new(AtomicBool::new(false));
+        let has_output = Arc::new(AtomicBool::new(false));
+        let title = SharedTitle::default();
+        let event_proxy = EventProxy::new(
+            event_sender,
+            generation.clone(),
+            exited.clone(),
+            has_output.clone(),
+            title.clone(),
+        );
+        let scroll_counter = SharedScrollCounter::new(Mutex::new(
+            ScrollCounter::new(config.scrolling_history),
+        ));
+        let mut term = Term::new(config, &terminal_size, event_proxy.clone());
+        let initial_content = RenderableContent::new(&mut term, terminal_size);
         let term = Arc::new(FairMutex::new(term));
+        let output_tap = SharedPtyTap::default();
+        let prompt_marks = SharedPromptMarks::default();
+        let directory = SharedDirectory::default();
+        let mut prompt_recorder = PromptMarkRecorder::new(
+            term.clone(),
+            prompt_marks.clone(),
+            directory.clone(),
+            scroll_counter.clone(),
+        );
+        let mut pty = TappedPty::new(
+            pty,
+            output_tap.clone(),
+            Box::new(move |bytes| prompt_recorder.scan(bytes)),
+            (!allow_alt_screen).then(AltScreenFilter::default),
+        );
+        pty.on_resize(terminal_size.into());
         let pty_event_loop =
-            EventLoop::new(term.clone(), event_proxy, pty, false, false)?;
+            EventLoop::new(term.clone(), event_proxy, pty, false, false)
+                .map_err(BackendError::PtyAllocation)?;
         let notifier = Notifier(pty_event_loop.channel());
-        let url_regex = RegexSearch::new(r#"(ipfs:|ipns:|magnet:|mailto:|gemini://|gopher://|https://|http://|news:|file://|git://|ssh:|ftp://)[^\u{0000}-\u{001F}\u{007F}-\u{009F}<>"\s{-}\^⟨⟩`]+"#).unwrap();
+        let pty_writer = Notifier(pty_event_loop.channel());
         let _pty_event_loop_thread = pty_event_loop.spawn();
-        let _pty_event_subscription = std::thread::Builder::new()
-            .name(format!("pty_event_subscription_{}", id))
-            .spawn(move || loop {
-                if let Ok(event) = event_receiver.recv() {
-                    pty_event_proxy_sender
-                        .send((id, event.clone()))
-                        .unwrap_or_else(|_| {
-                            panic!("pty_event_subscription_{}: sending PtyEvent is failed", id)
-                        });
-                    app_context.clone().request_repaint();
-                    if let Event::Exit = event {
-                        break;
-                    }
-                }
-            })?;
+        let keep_open_on_exit = options.keep_open_on_exit;
+        spawn_pty_event_subscription(
+            id,
+            app_context.clone(),
+            event_receiver,
+            pty_event_proxy_sender.clone(),
+            Some(pty_writer),
+            options,
+        )
+        .map_err(BackendError::ThreadSpawn)?;
 
         Ok(Self {
             id,
             url_regex,
             term: term.clone(),
             size: terminal_size,
-            notifier,
+            notifier: Some(notifier),
+            child_pid,
+            parser: ansi::Processor::new(),
             last_content: initial_content,
+            generation,
+            synced_generation: 0,
+            exited,
+            keep_open_on_exit,
+            has_output,
+            title,
+            app_context,
+            pty_event_proxy_sender,
+            settings: None,
+            input_tap: SharedPtyTap::default(),
+            output_tap,
+            backspace_sends_delete: true,
+            confirm_multiline_paste: false,
+            scroll_on_output: ScrollOnOutput::default(),
+            held_viewport: None,
+            pending_paste: None,
+            marks: Marks::default(),
+            resize_callback: None,
+            prompt_marks,
+            scroll_counter,
+            osc_parser: OscParser::default(),
+            directory,
+            #[cfg(unix)]
+            master_fd: None,
         })
     }
 
+    /// Creates a backend without a PTY and child process. The grid is
+    /// filled only by bytes passed to [`TerminalBackend::feed_bytes`], so
+    /// it can be used to play back recorded sessions. Input is discarded.
+    /// Needs a Tokio runtime like [`TerminalBackend::new`].
+    pub fn new_replay(
+        id: u64,
+        app_context: egui::Context,
+        pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
+    ) -> Result<Self> {
+        let config = term::Config::default();
+        let terminal_size = TerminalSize::default();
+        let (event_sender, event_receiver) = event_channel();
+        let generation = Arc::new(AtomicU64::new(0));
+        let exited = Arc::new(AtomicBool::new(false));
+        let has_output = Arc::new(AtomicBool::new(false));
+        let title = SharedTitle::default();
+        let event_proxy = EventProxy::new(
+            event_sender,
+            generation.clone(),
+            exited.clone(),
+            has_output.clone(),
+            title.clone(),
+        );
+        let scroll_counter = SharedScrollCounter::new(Mutex::new(
+            ScrollCounter::new(config.scrolling_history),
+        ));
+        let mut term = Term::new(config, &terminal_size, event_proxy);
+        let initial_content = RenderableContent::new(&mut term, terminal_size);
+        spawn_pty_event_subscription(
+            id,
+            app_context.clone(),
+            event_receiver,
+            pty_event_proxy_sender.clone(),
+            None,
+            SubscriptionOptions::default(),
+        )?;
+
+        Ok(Self {
+            id,
+            url_regex: default_url_regex()?,
+            term: Arc::new(FairMutex::new(term)),
+            size: terminal_size,
+            notifier: None,
+            child_pid: None,
+            parser: ansi::Processor::new(),
+            last_content: initial_content,
+            generation,
+            synced_generation: 0,
+            exited,
+            keep_open_on_exit: false,
+            has_output,
+            title,
+            app_context,
+            pty_event_proxy_sender,
+            settings: None,
+            input_tap: SharedPtyTap::default(),
+            output_tap: SharedPtyTap::default(),
+            backspace_sends_delete: true,
+            confirm_multiline_paste: false,
+            scroll_on_output: ScrollOnOutput::default(),
+            held_viewport: None,
+            pending_paste: None,
+            marks: Marks::default(),
+            resize_callback: None,
+            prompt_marks: SharedPromptMarks::default(),
+            scroll_counter,
+            osc_parser: OscParser::default(),
+            directory: SharedDirectory::default(),
+            #[cfg(unix)]
+            master_fd: None,
+        })
+    }
+
+    /// Shuts down the shell and spawns a new one with the original
+    /// settings, keeping the id, size and taps. The grid is cleared. Only
+    /// backends created with [`TerminalBackend::new`] can be restarted.
+    pub fn restart(&mut self) -> Result<()> {
+        let Some(settings) = self.settings.clone() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "backend was not created from BackendSettings",
+            ));
+        };
+
+        let mut backend = Self::try_new(
+            self.id,
+            self.app_context.clone(),
+            self.pty_event_proxy_sender.clone(),
+            settings,
+        )?;
+        std::mem::swap(&mut backend.url_regex, &mut self.url_regex);
+        if let Some(tap) = self.input_tap.take() {
+            backend.input_tap.set(tap);
+        }
+        if let Some(tap) = self.output_tap.take() {
+            backend.output_tap.set(tap);
+        }
+        backend.process_command(BackendCommand::Resize(
+            self.size.layout_size,
+            Size::new(
+                self.size.cell_width as f32,
+                self.size.cell_height as f32,
+            ),
+        ));
+
+        // Moved after the resize, which only restores the size the
+        // application already knows.
+        backend.resize_callback = self.resize_callback.take();
+
+        // Dropping the old backend shuts down its event loop.
+        *self = backend;
+        Ok(())
+    }
+
+    /// Types `key` as if it was pressed in a view with `bindings_layout`,
+    /// resolving it for the current terminal mode. A page scroll moves by
+    /// the screen less one line. Copy and paste need the app's clipboard,
+    /// so they are returned instead.
+    pub fn send_key(
+        &mut self,
+        bindings_layout: &BindingsLayout,
+        key: Key,
+        modifiers: Modifiers,
+    ) -> Option<ClipboardRequest> {
+        let screen_lines = self.last_content.terminal_size.screen_lines();
+        let page_lines = screen_lines.saturating_sub(1).max(1) as i32;
+        match self.key_action(bindings_layout, key, modifiers, page_lines)? {
+            KeyAction::Command(cmd) => {
+                self.process_command(cmd);
+                None
+            },
+            KeyAction::Clipboard(request) => {
+                // Copying leaves copy mode, like in the view.
+                if matches!(request, ClipboardRequest::Copy(_))
+                    && self.last_content.terminal_mode.contains(TermMode::VI)
+                {
+                    self.process_command(BackendCommand::ToggleCopyMode);
+                }
+                Some(request)
+            },
+        }
+    }
+
+    /// Resolves a key press to what it triggers, if anything.
+    pub(crate) fn key_action(
+        &self,
+        bindings_layout: &BindingsLayout,
+        key: Key,
+        modifiers: Modifiers,
+        page_lines: i32,
+    ) -> Option<KeyAction> {
+        // A copy binding copies the selection. Without one the key types as
+        // usual, so a copy binding on Ctrl+C still interrupts.
+        let terminal_mode = self.binding_mode();
+        if self.has_selection()
+            && bindings_layout.has_action(
+                InputKind::KeyCode(key),
+                modifiers,
+                terminal_mode,
+                &BindingAction::Copy,
+            )
+        {
+            let text = self.selectable_content();
+            return Some(KeyAction::Clipboard(ClipboardRequest::Copy(text)));
+        }
+
+        // Keys don't reach the PTY in copy mode.
+        if let Some(mut bytes) = bindings_layout
+            .key_bytes(key, modifiers, terminal_mode)
+            .filter(|_| !terminal_mode.contains(TermMode::VI))
+        {
+            if key == Key::Backspace && !self.backspace_sends_delete {
+                swap_backspace_codes(&mut bytes);
+            }
+            return Some(KeyAction::Command(BackendCommand::Write(bytes)));
+        }
+
+        let cmd = match bindings_layout.get_action(
+            InputKind::KeyCode(key),
+            modifiers,
+            terminal_mode,
+        ) {
+            BindingAction::ClearSelection => BackendCommand::ClearSelection,
+            BindingAction::SelectAll => BackendCommand::SelectAll,
+            BindingAction::ToggleCopyMode => BackendCommand::ToggleCopyMode,
+            BindingAction::CopyModeMotion(motion) => {
+                BackendCommand::CopyModeMotion(motion)
+            },
+            BindingAction::CopyModeSelect => BackendCommand::CopyModeSelect,
+            BindingAction::ScrollPageUp => BackendCommand::Scroll(page_lines),
+            BindingAction::ScrollPageDown => {
+                BackendCommand::Scroll(-page_lines)
+            },
+            BindingAction::Paste => {
+                return Some(KeyAction::Clipboard(ClipboardRequest::Paste));
+            },
+            _ => return None,
+        };
+        Some(KeyAction::Command(cmd))
+    }
+
+    /// Creates a replay backend showing a state saved with
+    /// [`TerminalBackend::export_state`], e.g. by a previous run of the app.
+    pub fn from_state(
+        id: u64,
+        app_context: egui::Context,
+        pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
+        state: &TerminalStateBlob,
+    ) -> Result<Self> {
+        let mut backend =
+            Self::new_replay(id, app_context, pty_event_proxy_sender)?;
+        backend.restore_state(state);
+        Ok(backend)
+    }
+
+    /// Saves the scrollback, screen and cursor position, so they can be
+    /// restored after the app restarts.
+    pub fn export_state(&self) -> TerminalStateBlob {
+        state::export_state(&self.term.lock())
+    }
+
+    /// Writes a saved state into the grid. On a backend with a shell, do it
+    /// before the shell prints its prompt.
+    pub fn restore_state(&mut self, state: &TerminalStateBlob) {
+        self.feed_bytes(state.as_bytes());
+    }
+
+    /// Pushes bytes straight through the parser into the grid, as if they
+    /// were read from the PTY. When the backend owns a live PTY, the bytes
+    /// may interleave with the shell output.
+    pub fn feed_bytes(&mut self, bytes: &[u8]) {
+        self.output_tap.call(bytes);
+        self.has_output.store(true, Ordering::Relaxed);
+        self.mark_dirty();
+        let term = self.term.clone();
+        let mut term = term.lock();
+        for byte in bytes {
+            self.parser.advance(&mut *term, *byte);
+            // Counted per line, so long output doesn't outgrow the room the
+            // counter leaves in the history.
+            if *byte == b'\n' {
+                self.scrolled_lines(&mut term);
+            }
+            match self.osc_parser.advance(*byte) {
+                Some(ShellOsc::PromptMark(kind)) => {
+                    let scrolled = self.scrolled_lines(&mut term);
+                    let line = term.grid().cursor.point.line;
+                    if let Ok(mut marks) = self.prompt_marks.lock() {
+                        marks.push(kind, line, scrolled);
+                    }
+                },
+                Some(ShellOsc::Directory(path)) => {
+                    if let Ok(mut directory) = self.directory.lock() {
+                        *directory = Some(path);
+                    }
+                },
+                None => {},
+            }
+        }
+    }
+
+    /// Sets a callback that receives every chunk of bytes read from the PTY
+    /// and when it was read. It is invoked on the PTY event loop thread
+    /// before the bytes are parsed.
+    pub fn set_output_tap(&mut self, tap: PtyTap) {
+        self.output_tap.set(tap);
+    }
+
+    /// Sets a callback that receives every chunk of bytes written to the PTY
+    /// and when it was written.
+    pub fn set_input_tap(&mut self, tap: PtyTap) {
+        self.input_tap.set(tap);
+    }
+
+    /// Sets a callback invoked whenever the number of columns or rows
+    /// changes, e.g. to show "80x24" without comparing sizes every frame.
+    pub fn set_resize_callback(&mut self, callback: ResizeCallback) {
+        self.resize_callback = Some(callback);
+    }
+
     pub fn process_command(&mut self, cmd: BackendCommand) {
         let term = self.term.clone();
         let mut term = term.lock();
@@ -206,18 +785,30 @@ impl TerminalBackend {
             BackendCommand::Write(input) => {
                 self.write(input);
                 term.scroll_display(Scroll::Bottom);
+                self.mark_dirty();
+            },
+            BackendCommand::WriteNoScroll(input) => {
+                self.write(input);
+            },
+            BackendCommand::Paste(text) => {
+                self.write_paste(*term.mode(), text);
+                term.scroll_display(Scroll::Bottom);
+                self.mark_dirty();
             },
             BackendCommand::Scroll(delta) => {
                 self.scroll(&mut term, delta);
+                self.mark_dirty();
             },
             BackendCommand::Resize(layout_size, font_size) => {
                 self.resize(&mut term, layout_size, font_size);
             },
             BackendCommand::SelectStart(selection_type, x, y) => {
                 self.start_selection(&mut term, selection_type, x, y);
+                self.mark_dirty();
             },
             BackendCommand::SelectUpdate(x, y) => {
                 self.update_selection(&mut term, x, y);
+                self.mark_dirty();
             },
             BackendCommand::ProcessLink(link_action, point) => {
                 self.process_link_action(&term, link_action, point);
@@ -225,6 +816,30 @@ impl TerminalBackend {
             BackendCommand::MouseReport(button, modifiers, point, pressed) => {
                 self.process_mouse_report(button, modifiers, point, pressed);
             },
+            BackendCommand::ClearSelection => {
+                self.reset_selection(&mut term);
+            },
+            BackendCommand::SelectAll => {
+                self.select_all(&mut term);
+                self.mark_dirty();
+            },
+            BackendCommand::ToggleCopyMode => {
+                term.toggle_vi_mode();
+                if !term.mode().contains(TermMode::VI) {
+                    self.reset_selection(&mut term);
+                }
+                self.mark_dirty();
+            },
+            BackendCommand::CopyModeMotion(motion) => {
+                // Also moves the end of the selection and scrolls the
+                // cursor into view.
+                term.vi_motion(motion.into());
+                self.mark_dirty();
+            },
+            BackendCommand::CopyModeSelect => {
+                self.toggle_copy_mode_selection(&mut term);
+                self.mark_dirty();
+            },
         };
     }
 
@@ -244,146 +859,603 @@ impl TerminalBackend {
     }
 
     pub fn selectable_content(&self) -> String {
-        let content = self.last_content();
-        let mut result = String::new();
-        if let Some(range) = content.selectable_range {
-            for indexed in content.grid.display_iter() {
-                if range.contains(indexed.point) {
-                    result.push(indexed.c);
-                }
-            }
-        }
-        result
-    }
-
-    pub fn sync(&mut self) -> &RenderableContent {
-        let term = self.term.clone();
-        let mut terminal = term.lock();
-        let selectable_range = match &terminal.selection {
-            Some(s) => s.to_range(&terminal),
-            None => None,
-        };
-
-        let cursor = terminal.grid_mut().cursor_cell().clone();
-        self.last_content.grid = terminal.grid().clone();
-        self.last_content.selectable_range = selectable_range;
-        self.last_content.cursor = cursor.clone();
-        self.last_content.terminal_mode = *terminal.mode();
-        self.last_content.terminal_size = self.size;
-        self.last_content()
+        self.term.lock().selection_to_string().unwrap_or_default()
     }
 
-    pub fn last_content(&self) -> &RenderableContent {
-        &self.last_content
+    /// Raw master side of the pty, for integrations such as custom flow
+    /// control. The crate's event loop keeps reading and writing it, so
+    /// using it concurrently is unsupported. `None` for replay backends.
+    #[cfg(unix)]
+    pub fn master_fd(&self) -> Option<RawFd> {
+        self.master_fd
     }
 
-    fn process_link_action(
-        &mut self,
-        terminal: &Term<EventProxy>,
-        link_action: LinkAction,
-        point: Point,
-    ) {
-        match link_action {
-            LinkAction::Hover => {
-                self.last_content.hovered_hyperlink = self.regex_match_at(
-                    terminal,
-                    point,
-                    &mut self.url_regex.clone(),
-                );
-            },
-            LinkAction::Clear => {
-                self.last_content.hovered_hyperlink = None;
+    /// Finds all matches of `pattern` on the active screen, in grid
+    /// coordinates. While a full screen program uses the alternate screen,
+    /// only its buffer is searched and the main screen history is not.
+    pub fn search(
+        &self,
+        pattern: &str,
+        scope: SearchScope,
+    ) -> std::result::Result<Vec<Match>, BackendError> {
+        let mut regex = RegexSearch::new(pattern)
+            .map_err(|err| BackendError::RegexCompile(err.to_string()))?;
+        let terminal = self.term.lock();
+        let (start, end) = match scope {
+            SearchScope::Visible => {
+                let top = Line(-(terminal.grid().display_offset() as i32));
+                (top, top + terminal.bottommost_line())
             },
-            LinkAction::Open => {
-                self.open_link();
+            SearchScope::History => {
+                (terminal.topmost_line(), terminal.bottommost_line())
             },
         };
-    }
 
-    fn open_link(&self) {
-        if let Some(range) = &self.last_content.hovered_hyperlink {
-            let start = range.start();
-            let end = range.end();
+        Ok(RegexIter::new(
+            Point::new(start, Column(0)),
+            Point::new(end, terminal.last_column()),
+            Direction::Right,
+            &terminal,
+            &mut regex,
+        )
+        .collect())
+    }
 
-            let mut url = String::from(self.last_content.grid.index(*start).c);
-            for indexed in self.last_content.grid.iter_from(*start) {
-                url.push(indexed.c);
-                if indexed.point == *end {
-                    break;
-                }
-            }
+    /// Sends `signal` (e.g. `libc::SIGINT`) to the foreground process group
+    /// of the pty, so it reaches the running command rather than the
+    /// shell. Falls back to the shell process when the group is unknown.
+    #[cfg(unix)]
+    pub fn send_signal(&self, signal: i32) -> Result<()> {
+        let foreground_group = self
+            .master_fd
+            .map(|fd| unsafe { libc::tcgetpgrp(fd) })
+            .filter(|pgrp| *pgrp > 0);
+        let target = match (foreground_group, self.child_pid) {
+            (Some(pgrp), _) => -pgrp,
+            (None, Some(pid)) => pid as libc::pid_t,
+            (None, None) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "no process to signal",
+                ));
+            },
+        };
 
-            open::that(url).unwrap_or_else(|_| {
-                panic!("link opening is failed");
-            })
+        if unsafe { libc::kill(target, signal) } == -1 {
+            return Err(std::io::Error::last_os_error());
         }
+
+        Ok(())
     }
 
-    fn process_mouse_report(
-        &self,
-        button: MouseButton,
-        modifiers: Modifiers,
-        point: Point,
-        pressed: bool,
-    ) {
-        let mut mods = 0;
-        if modifiers.contains(Modifiers::SHIFT) {
-            mods += 4;
-        }
-        if modifiers.contains(Modifiers::ALT) {
-            mods += 8;
-        }
-        if modifiers.contains(Modifiers::COMMAND) {
-            mods += 16;
-        }
+    /// Whether the shell has exited. The grid keeps its last content.
+    pub fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::Relaxed)
+    }
 
-        match MouseMode::from(self.last_content().terminal_mode) {
-            MouseMode::Sgr => {
-                self.sgr_mouse_report(point, button as u8 + mods, pressed)
-            },
-            MouseMode::Normal(is_utf8) => {
-                if pressed {
-                    self.normal_mouse_report(
-                        point,
-                        button as u8 + mods,
-                        is_utf8,
-                    )
-                } else {
-                    self.normal_mouse_report(point, 3 + mods, is_utf8)
-                }
-            },
-        }
+    /// Whether the shell has exited and the terminal stays open with its
+    /// final output, see [`BackendSettings::keep_open_on_exit`].
+    pub fn is_kept_open(&self) -> bool {
+        self.keep_open_on_exit && self.has_exited()
     }
 
-    fn sgr_mouse_report(&self, point: Point, button: u8, pressed: bool) {
-        let c = if pressed { 'M' } else { 'm' };
+    /// Whether the program printed anything yet, or bytes were fed with
+    /// [`TerminalBackend::feed_bytes`].
+    pub fn has_output(&self) -> bool {
+        self.has_output.load(Ordering::Relaxed)
+    }
 
-        let msg = format!(
-            "\x1b[<{};{};{}{}",
-            button,
-            point.column + 1,
-            point.line + 1,
-            c
-        );
+    /// Current window title set by the program, None until it sets one or
+    /// after it reset it. Titles pushed with `CSI 22 ; 2 t` are restored by
+    /// `CSI 23 ; 2 t`, and the restored title is sent as
+    /// [`PtyEvent::Title`], or as [`PtyEvent::ResetTitle`] when no title
+    /// was set at the push.
+    pub fn title(&self) -> Option<String> {
+        self.title.lock().ok()?.clone()
+    }
 
-        self.notifier.notify(msg.as_bytes().to_vec());
+    /// Whether Backspace emits DEL (`0x7f`) rather than BS (`0x08`).
+    pub fn backspace_sends_delete(&self) -> bool {
+        self.backspace_sends_delete
     }
 
-    fn normal_mouse_report(&self, point: Point, button: u8, is_utf8: bool) {
-        let Point { line, column } = point;
-        let max_point = if is_utf8 { 2015 } else { 223 };
+    /// Changes what Backspace emits after the backend was created, see
+    /// [`BackendSettings::backspace_sends_delete`].
+    pub fn set_backspace_sends_delete(&mut self, backspace_sends_delete: bool) {
+        self.backspace_sends_delete = backspace_sends_delete;
+    }
 
-        if line >= max_point || column >= max_point {
+    /// Tells the backend whether its view has focus, for
+    /// [`ScrollOnOutput::Focused`]. `TerminalView` calls it every frame.
+    pub fn set_focused(&mut self, focused: bool) {
+        if self.scroll_on_output != ScrollOnOutput::Focused
+            || focused == self.held_viewport.is_none()
+        {
             return;
         }
 
-        let mut msg = vec![b'\x1b', b'[', b'M', 32 + button];
-
-        let mouse_pos_encode = |pos: usize| -> Vec<u8> {
-            let pos = 32 + 1 + pos;
-            let first = 0xC0 + pos / 64;
-            let second = 0x80 + (pos & 63);
-            vec![first as u8, second as u8]
+        let term = self.term.clone();
+        let mut term = term.lock();
+        if focused {
+            if let Some((_, true)) = self.held_viewport.take() {
+                term.scroll_display(Scroll::Bottom);
+                self.mark_dirty();
+            }
+        } else {
+            let scrolled = self.scrolled_lines(&mut term);
+            self.held_viewport =
+                Some((scrolled, term.grid().display_offset() == 0));
+        }
+    }
+
+    /// Sends `text` as if it was typed: line breaks are sent like Enter,
+    /// so as `\r` or as `\r\n` in line feed/new line mode, and the text
+    /// is never bracketed. Use it for macros and snippets.
+    pub fn type_text(&mut self, text: &str) {
+        let mode = *self.term.lock().mode();
+        let text = typed_text(text, mode);
+        self.process_command(BackendCommand::Write(text.into_bytes()));
+    }
+
+    /// Sends `text` as a paste, like [`BackendCommand::Paste`]. With
+    /// bracketed paste the program sees it as pasted, e.g. a shell doesn't
+    /// run its lines, otherwise `confirm_multiline_paste` may hold it back.
+    pub fn paste(&mut self, text: &str) {
+        self.process_command(BackendCommand::Paste(text.to_string()));
+    }
+
+    /// A multi-line paste held back by `confirm_multiline_paste`. The app
+    /// shows it for confirmation and then calls
+    /// [`TerminalBackend::confirm_paste`] or [`TerminalBackend::cancel_paste`].
+    pub fn pending_paste(&self) -> Option<&str> {
+        self.pending_paste.as_deref()
+    }
+
+    /// Sends the held back paste to the PTY.
+    pub fn confirm_paste(&mut self) {
+        if let Some(text) = self.pending_paste.take() {
+            self.process_command(BackendCommand::Write(text.into_bytes()));
+        }
+    }
+
+    /// Drops the held back paste.
+    pub fn cancel_paste(&mut self) {
+        self.pending_paste = None;
+    }
+
+    /// Text of a single line in grid coordinates, including the
+    /// scrollback. Lines outside the grid yield an empty string.
+    pub fn line_text(&self, line: Line) -> String {
+        let terminal = self.term.lock();
+        if line < terminal.topmost_line() || line > terminal.bottommost_line() {
+            return String::new();
+        }
+
+        terminal.bounds_to_string(
+            Point::new(line, Column(0)),
+            Point::new(line, terminal.last_column()),
+        )
+    }
+
+    /// Text between two points in grid coordinates, extracted the same way
+    /// as the selection text. The range is clamped to the grid.
+    pub fn range_text(&self, range: RangeInclusive<Point>) -> String {
+        let terminal = self.term.lock();
+        let start = range.start().grid_clamp(&*terminal, Boundary::Grid);
+        let end = range.end().grid_clamp(&*terminal, Boundary::Grid);
+        if start > end {
+            return String::new();
+        }
+
+        terminal.bounds_to_string(start, end)
+    }
+
+    /// Current working directory of the shell, so new terminals can be
+    /// opened in the same folder. Shells with OSC 7 integration report it,
+    /// otherwise it is resolved through `/proc`, only on Linux. Reported
+    /// directories that don't exist here, e.g. over ssh, are skipped.
+    pub fn current_directory(&self) -> Option<PathBuf> {
+        self.directory
+            .lock()
+            .ok()
+            .and_then(|directory| directory.clone())
+            .filter(|directory| directory.is_dir())
+            .or_else(|| process_directory(self.child_pid?))
+    }
+
+    /// Name of the program in the foreground of the pty, e.g. `vim` or the
+    /// shell itself, to label tabs when the program sets no title. It is
+    /// available on Linux and macOS.
+    pub fn foreground_process_name(&self) -> Option<String> {
+        process_name(self.foreground_process_id()?)
+    }
+
+    /// The leader of the foreground process group, or the shell when the
+    /// group is unknown.
+    #[cfg(unix)]
+    fn foreground_process_id(&self) -> Option<u32> {
+        self.master_fd
+            .map(|fd| unsafe { libc::tcgetpgrp(fd) })
+            .filter(|pgrp| *pgrp > 0)
+            .map(|pgrp| pgrp as u32)
+            .or(self.child_pid)
+    }
+
+    #[cfg(not(unix))]
+    fn foreground_process_id(&self) -> Option<u32> {
+        self.child_pid
+    }
+
+    /// Cursor of the last synced content in grid coordinates: `Line(0)` is
+    /// the top row of the live screen and the cursor never enters the
+    /// scrollback. Add `display_offset` to get the row in the viewport.
+    pub fn cursor_position(&self) -> Point {
+        self.last_content.cursor.point
+    }
+
+    /// Visible lines as `(top, bottom, total)`: the first and last visible
+    /// line, both inclusive and counted from the oldest line of the
+    /// scrollback, and the lines of the scrollback and the screen
+    /// together, e.g. to show "lines 1200-1250 of 9000".
+    pub fn scroll_region(&self) -> (usize, usize, usize) {
+        let terminal = self.term.lock();
+        let grid = terminal.grid();
+        let top = grid.history_size() - grid.display_offset();
+        (top, top + grid.screen_lines() - 1, grid.total_lines())
+    }
+
+    /// Marks the line of the cursor, e.g. where a command starts, to jump
+    /// back to it with [`TerminalBackend::scroll_to_mark`]. The mark moves
+    /// up with its line as output scrolls and is dropped when the line
+    /// leaves the scrollback.
+    pub fn add_mark(&mut self) -> MarkId {
+        let mut terminal = self.term.lock();
+        let line = terminal.grid().cursor.point.line;
+        let scrolled = self.scrolled_lines(&mut terminal);
+        let id = self.marks.add(line, scrolled);
+        self.mark_dirty();
+        id
+    }
+
+    pub fn remove_mark(&mut self, id: MarkId) {
+        self.marks.remove(id);
+        self.mark_dirty();
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marks.clear();
+        self.mark_dirty();
+    }
+
+    /// Lines scrolled into the history since the start, which marks are
+    /// shifted by.
+    fn scrolled_lines(&self, terminal: &mut Term<EventProxy>) -> i64 {
+        self.scroll_counter
+            .lock()
+            .map_or(0, |mut scroll_counter| scroll_counter.update(terminal))
+    }
+
+    /// Marks still in the scrollback with their lines in grid coordinates,
+    /// oldest first.
+    pub fn marks(&self) -> Vec<(MarkId, Line)> {
+        let mut terminal = self.term.lock();
+        self.marks.lines(
+            self.scrolled_lines(&mut terminal),
+            terminal.grid().history_size(),
+        )
+    }
+
+    /// Scrolls the viewport so the marked line is at the top, or as close
+    /// as the live screen allows. Returns false if the mark is gone.
+    pub fn scroll_to_mark(&mut self, id: MarkId) -> bool {
+        let line = {
+            let mut terminal = self.term.lock();
+            self.marks.line(
+                id,
+                self.scrolled_lines(&mut terminal),
+                terminal.grid().history_size(),
+            )
+        };
+        match line {
+            Some(line) => self.scroll_line_to_top(line),
+            None => false,
+        }
+    }
+
+    fn scroll_line_to_top(&mut self, line: Line) -> bool {
+        let mut terminal = self.term.lock();
+        let display_offset = terminal.grid().display_offset() as i32;
+        terminal.scroll_display(Scroll::Delta(-line.0.min(0) - display_offset));
+        drop(terminal);
+        self.mark_dirty();
+        true
+    }
+
+    /// Scrolls the previous prompt above the top of the viewport to the
+    /// top. Needs a shell with OSC 133 shell integration. Returns false if
+    /// there is no such prompt.
+    pub fn scroll_to_previous_prompt(&mut self) -> bool {
+        let top = Line(-(self.last_content.display_offset as i32));
+        let line = self.prompt_lines().rev().find(|line| *line < top);
+        line.is_some_and(|line| self.scroll_line_to_top(line))
+    }
+
+    /// Scrolls the next prompt below the top of the viewport to the top.
+    pub fn scroll_to_next_prompt(&mut self) -> bool {
+        let top = Line(-(self.last_content.display_offset as i32));
+        let line = self.prompt_lines().find(|line| *line > top);
+        line.is_some_and(|line| self.scroll_line_to_top(line))
+    }
+
+    /// Selects the lines printed by the last command that started output,
+    /// up to the prompt after it. Returns false if there is none.
+    pub fn select_last_command_output(&mut self) -> bool {
+        let prompt_marks = &self.last_content.prompt_marks;
+        let Some(start) = prompt_marks
+            .iter()
+            .rposition(|mark| mark.kind == PromptMarkKind::OutputStart)
+        else {
+            return false;
+        };
+
+        let first = prompt_marks[start].line;
+        let end = prompt_marks[start + 1..]
+            .iter()
+            .find(|mark| {
+                matches!(
+                    mark.kind,
+                    PromptMarkKind::CommandFinished { .. }
+                        | PromptMarkKind::PromptStart
+                )
+            })
+            .map_or(self.last_content.cursor.point.line, |mark| mark.line);
+        // The next prompt starts on its own line unless the output was
+        // empty.
+        let last = if end > first { end - 1 } else { first };
+
+        let mut terminal = self.term.lock();
+        let last_column = terminal.grid().last_column();
+        let mut selection = Selection::new(
+            AlacrittySelectionType::Lines,
+            Point::new(first, Column(0)),
+            Side::Left,
+        );
+        selection.update(Point::new(last, last_column), Side::Right);
+        terminal.selection = Some(selection);
+        drop(terminal);
+        self.mark_dirty();
+        true
+    }
+
+    fn prompt_lines(&self) -> impl DoubleEndedIterator<Item = Line> + '_ {
+        self.last_content
+            .prompt_marks
+            .iter()
+            .filter(|mark| mark.kind == PromptMarkKind::PromptStart)
+            .map(|mark| mark.line)
+    }
+
+    /// Visible cells of the last synced content, for painting custom
+    /// overlays aligned to the grid with [`RenderableContent::cell_rect`].
+    pub fn visible_cells(&self) -> impl Iterator<Item = Indexed<&Cell>> {
+        self.last_content.display_iter()
+    }
+
+    /// Terminal mode bindings are looked up in, with [`SELECTION_MODE`]
+    /// while a selection exists.
+    fn binding_mode(&self) -> TermMode {
+        let mut terminal_mode = self.last_content.terminal_mode;
+        if self.has_selection() {
+            terminal_mode.insert(SELECTION_MODE);
+        }
+        terminal_mode
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.term
+            .lock()
+            .selection
+            .as_ref()
+            .is_some_and(|selection| !selection.is_empty())
+    }
+
+    pub fn clear_selection(&mut self) {
+        let term = self.term.clone();
+        let mut term = term.lock();
+        self.reset_selection(&mut term);
+    }
+
+    /// Returns true when the terminal changed since the last
+    /// [`TerminalBackend::sync`].
+    pub fn is_dirty(&self) -> bool {
+        self.generation.load(Ordering::Relaxed) != self.synced_generation
+    }
+
+    /// Whether painting the terminal would show something new. With
+    /// `repaint_on_output` disabled, the app can poll this to decide when
+    /// to request a repaint.
+    pub fn wants_repaint(&self) -> bool {
+        self.is_dirty()
+    }
+
+    /// Waits until the bytes the PTY holds now are parsed and syncs the
+    /// content, so tests and "wait until output settles" logic don't have
+    /// to sleep for the event loop. Returns whether anything new was
+    /// applied. Gives up waiting after 100 ms of continuous output.
+    pub fn pump(&mut self) -> bool {
+        #[cfg(unix)]
+        if let Some(fd) = self.master_fd {
+            let deadline = std::time::Instant::now() + PUMP_TIMEOUT;
+            while pending_pty_bytes(fd) > 0
+                && std::time::Instant::now() < deadline
+            {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        // The event loop holds the lock while it parses what it read.
+        drop(self.term.lock());
+        let synced_generation = self.synced_generation;
+        self.sync();
+        self.synced_generation != synced_generation
+    }
+
+    pub fn sync(&mut self) -> &RenderableContent {
+        // Read the generation before taking the lock, so output parsed
+        // while cloning is picked up by the next sync.
+        let generation = self.generation.load(Ordering::Relaxed);
+        if generation == self.synced_generation {
+            return self.last_content();
+        }
+
+        let term = self.term.clone();
+        let mut terminal = term.lock();
+        let selectable_range = match &terminal.selection {
+            Some(s) => s.to_range(&terminal),
+            None => None,
+        };
+
+        let scrolled = self.scrolled_lines(&mut terminal);
+        if let Some((held_scrolled, _)) = &mut self.held_viewport {
+            // The grid keeps the viewport only once it is scrolled back, so
+            // it is moved up by the lines scrolled in from the bottom.
+            let scrolled_in = scrolled - *held_scrolled;
+            *held_scrolled = scrolled;
+            if terminal.grid().display_offset() == 0 && scrolled_in > 0 {
+                terminal.scroll_display(Scroll::Delta(scrolled_in as i32));
+            }
+        }
+
+        self.last_content.viewport = viewport_rows(terminal.grid());
+        self.last_content.display_offset = terminal.grid().display_offset();
+        self.last_content.selectable_range = selectable_range;
+        self.last_content.cursor = cursor(&mut terminal);
+        let history_size = terminal.grid().history_size();
+        self.last_content.marks = self
+            .marks
+            .lines(scrolled, history_size)
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect();
+        if let Ok(mut prompt_marks) = self.prompt_marks.lock() {
+            self.last_content.prompt_marks =
+                prompt_marks.lines(scrolled, history_size);
+        }
+        self.last_content.copy_mode_cursor = terminal
+            .mode()
+            .contains(TermMode::VI)
+            .then_some(terminal.vi_mode_cursor.point);
+        self.last_content.terminal_mode = *terminal.mode();
+        self.last_content.terminal_size = self.size;
+        self.synced_generation = generation;
+        self.last_content()
+    }
+
+    fn mark_dirty(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn last_content(&self) -> &RenderableContent {
+        &self.last_content
+    }
+
+    fn process_link_action(
+        &mut self,
+        terminal: &Term<EventProxy>,
+        link_action: LinkAction,
+        point: Point,
+    ) {
+        match link_action {
+            LinkAction::Hover => {
+                self.last_content.hovered_hyperlink = self.regex_match_at(
+                    terminal,
+                    point,
+                    &mut self.url_regex.clone(),
+                );
+            },
+            LinkAction::Clear => {
+                self.last_content.hovered_hyperlink = None;
+            },
+            LinkAction::Open => {
+                self.open_link(terminal);
+            },
+        };
+    }
+
+    fn open_link(&self, terminal: &Term<EventProxy>) {
+        if let Some(range) = &self.last_content.hovered_hyperlink {
+            let url = terminal.bounds_to_string(*range.start(), *range.end());
+            open::that(url).unwrap_or_else(|_| {
+                panic!("link opening is failed");
+            })
+        }
+    }
+
+    fn process_mouse_report(
+        &self,
+        button: MouseButton,
+        modifiers: Modifiers,
+        point: Point,
+        pressed: bool,
+    ) {
+        let mut mods = 0;
+        if modifiers.contains(Modifiers::SHIFT) {
+            mods += 4;
+        }
+        if modifiers.contains(Modifiers::ALT) {
+            mods += 8;
+        }
+        if modifiers.contains(Modifiers::COMMAND) {
+            mods += 16;
+        }
+
+        match MouseMode::from(self.last_content().terminal_mode) {
+            MouseMode::Sgr => {
+                self.sgr_mouse_report(point, button as u8 + mods, pressed)
+            },
+            MouseMode::Normal(is_utf8) => {
+                if pressed {
+                    self.normal_mouse_report(
+                        point,
+                        button as u8 + mods,
+                        is_utf8,
+                    )
+                } else {
+                    self.normal_mouse_report(point, 3 + mods, is_utf8)
+                }
+            },
+        }
+    }
+
+    fn sgr_mouse_report(&self, point: Point, button: u8, pressed: bool) {
+        let c = if pressed { 'M' } else { 'm' };
+
+        let msg = format!(
+            "\x1b[<{};{};{}{}",
+            button,
+            point.column + 1,
+            point.line + 1,
+            c
+        );
+
+        self.write(msg.as_bytes().to_vec());
+    }
+
+    fn normal_mouse_report(&self, point: Point, button: u8, is_utf8: bool) {
+        let Point { line, column } = point;
+        let max_point = if is_utf8 { 2015 } else { 223 };
+
+        if line >= max_point || column >= max_point {
+            return;
+        }
+
+        let mut msg = vec![b'\x1b', b'[', b'M', 32 + button];
+
+        let mouse_pos_encode = |pos: usize| -> Vec<u8> {
+            let pos = 32 + 1 + pos;
+            let first = 0xC0 + pos / 64;
+            let second = 0x80 + (pos & 63);
+            vec![first as u8, second as u8]
         };
 
         if is_utf8 && column >= Column(95) {
@@ -398,7 +1470,13 @@ impl TerminalBackend {
             msg.push(32 + 1 + line.0 as u8);
         }
 
-        self.notifier.notify(msg);
+        self.write(msg);
+    }
+
+    fn reset_selection(&mut self, terminal: &mut Term<EventProxy>) {
+        terminal.selection = None;
+        self.last_content.selectable_range = None;
+        self.mark_dirty();
     }
 
     fn start_selection(
@@ -408,17 +1486,42 @@ impl TerminalBackend {
         x: f32,
         y: f32,
     ) {
-        let location = Self::selection_point(
-            x,
-            y,
-            &self.size,
-            terminal.grid().display_offset(),
+        let (location, side) = self.hit_test(terminal, x, y);
+        terminal.selection =
+            Some(Selection::new(selection_type, location, side));
+    }
+
+    /// Starts a selection at the copy mode cursor, or drops the current one.
+    fn toggle_copy_mode_selection(&mut self, terminal: &mut Term<EventProxy>) {
+        if !terminal.mode().contains(TermMode::VI) {
+            return;
+        }
+
+        if terminal.selection.is_some() {
+            self.reset_selection(terminal);
+            return;
+        }
+
+        let mut selection = Selection::new(
+            AlacrittySelectionType::Simple,
+            terminal.vi_mode_cursor.point,
+            Side::Left,
         );
-        terminal.selection = Some(Selection::new(
-            selection_type,
-            location,
-            self.selection_side(x),
-        ));
+        // Includes the cell under the cursor, so motions update it.
+        selection.include_all();
+        terminal.selection = Some(selection);
+    }
+
+    /// Selects from the top of the scrollback to the last cell, independent
+    /// of the display offset.
+    fn select_all(&mut self, terminal: &mut Term<EventProxy>) {
+        let start = Point::new(terminal.topmost_line(), Column(0));
+        let end =
+            Point::new(terminal.bottommost_line(), terminal.last_column());
+        let mut selection =
+            Selection::new(AlacrittySelectionType::Simple, start, Side::Left);
+        selection.update(end, Side::Right);
+        terminal.selection = Some(selection);
     }
 
     fn update_selection(
@@ -427,11 +1530,34 @@ impl TerminalBackend {
         x: f32,
         y: f32,
     ) {
-        let display_offset = terminal.grid().display_offset();
+        let (location, side) = self.hit_test(terminal, x, y);
         if let Some(ref mut selection) = terminal.selection {
-            let location =
-                Self::selection_point(x, y, &self.size, display_offset);
-            selection.update(location, self.selection_side(x));
+            selection.update(location, side);
+        }
+    }
+
+    /// The cell and side under a pixel position. A wide char spans its own
+    /// cell and the spacer after it, so its halves are whole cells and the
+    /// spacer maps back to the char.
+    fn hit_test(
+        &self,
+        terminal: &Term<EventProxy>,
+        x: f32,
+        y: f32,
+    ) -> (Point, Side) {
+        let point = Self::selection_point(
+            x,
+            y,
+            &self.size,
+            terminal.grid().display_offset(),
+        );
+        let flags = terminal.grid()[point].flags;
+        if flags.contains(cell::Flags::WIDE_CHAR_SPACER) && point.column.0 > 0 {
+            (Point::new(point.line, point.column - 1), Side::Right)
+        } else if flags.contains(cell::Flags::WIDE_CHAR) {
+            (point, Side::Left)
+        } else {
+            (point, self.selection_side(x))
         }
     }
 
@@ -462,6 +1588,8 @@ impl TerminalBackend {
         let lines = (layout_size.height / font_size.height.floor()) as u16;
         let cols = (layout_size.width / font_size.width.floor()) as u16;
         if lines > 0 && cols > 0 {
+            let is_grid_changed =
+                cols != self.size.num_cols || lines != self.size.num_lines;
             self.size = TerminalSize {
                 layout_size,
                 cell_height: font_size.height as u16,
@@ -470,16 +1598,45 @@ impl TerminalBackend {
                 num_cols: cols,
             };
 
-            self.notifier.on_resize(self.size.into());
-            terminal.resize(TermSize::new(
-                self.size.num_cols as usize,
-                self.size.num_lines as usize,
-            ));
+            if let Some(notifier) = &mut self.notifier {
+                notifier.on_resize(self.size.into());
+            }
+            resize_grid(terminal, self.size.num_cols, self.size.num_lines);
+            self.mark_dirty();
+            if is_grid_changed {
+                if let Some(callback) = &mut self.resize_callback {
+                    callback(GridSize {
+                        cols,
+                        rows: lines,
+                        size: Vec2::new(layout_size.width, layout_size.height),
+                    });
+                }
+            }
+        }
+    }
+
+    /// With bracketed paste the program can tell pasted from typed text,
+    /// so only plain pastes with newlines wait for confirmation.
+    fn write_paste(&mut self, terminal_mode: TermMode, text: String) {
+        if terminal_mode.contains(TermMode::BRACKETED_PASTE) {
+            // Any escape could end the paste early or smuggle in a
+            // sequence, so none of them reach the PTY.
+            let text = text.replace(['\x1b', '\x03'], "");
+            self.write(format!("\x1b[200~{}\x1b[201~", text).into_bytes());
+        } else if self.confirm_multiline_paste && text.contains(['\n', '\r']) {
+            self.pending_paste = Some(text);
+            self.app_context.request_repaint();
+        } else {
+            self.write(text.into_bytes());
         }
     }
 
     fn write<I: Into<Cow<'static, [u8]>>>(&self, input: I) {
-        self.notifier.notify(input);
+        let input = input.into();
+        self.input_tap.call(&input);
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(input);
+        }
     }
 
     fn scroll(&mut self, terminal: &mut Term<EventProxy>, delta_value: i32) {
@@ -498,7 +1655,7 @@ impl TerminalBackend {
                     content.push(line_cmd);
                 }
 
-                self.notifier.notify(content);
+                self.write(content);
             } else {
                 terminal.grid_mut().scroll_display(scroll);
             }
@@ -519,15 +1676,278 @@ impl TerminalBackend {
     }
 }
 
-/// Copied from alacritty/src/display/hint.rs:
-/// Iterate over all visible regex matches.
-fn visible_regex_match_iter<'a>(
-    term: &'a Term<EventProxy>,
-    regex: &'a mut RegexSearch,
-) -> impl Iterator<Item = Match> + 'a {
-    let viewport_start = Line(-(term.grid().display_offset() as i32));
-    let viewport_end = viewport_start + term.bottommost_line();
-    let mut start =
+#[cfg(target_os = "linux")]
+fn process_directory(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_directory(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn process_name(pid: u32) -> Option<String> {
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(name.trim_end().to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn process_name(pid: u32) -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let len = unsafe {
+        libc::proc_name(
+            pid as libc::c_int,
+            buffer.as_mut_ptr().cast(),
+            buffer.len() as u32,
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&buffer[..len as usize]).into_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn process_name(_pid: u32) -> Option<String> {
+    None
+}
+
+/// Replaces the line breaks of typed text with what Enter sends.
+fn typed_text(text: &str, mode: TermMode) -> String {
+    let enter = if mode.contains(TermMode::LINE_FEED_NEW_LINE) {
+        "\r\n"
+    } else {
+        "\r"
+    };
+    text.replace("\r\n", "\n").replace(['\r', '\n'], enter)
+}
+
+fn default_url_regex() -> std::result::Result<RegexSearch, BackendError> {
+    RegexSearch::new(r#"(ipfs:|ipns:|magnet:|mailto:|gemini://|gopher://|https://|http://|news:|file://|git://|ssh:|ftp://)[^\u{0000}-\u{001F}\u{007F}-\u{009F}<>"\s{-}\^⟨⟩`]+"#)
+        .map_err(|err| BackendError::RegexCompile(err.to_string()))
+}
+
+/// Exchanges DEL and BS, so Backspace and its modified variants follow
+/// [`TerminalBackend::backspace_sends_delete`].
+fn swap_backspace_codes(bytes: &mut [u8]) {
+    for byte in bytes {
+        *byte = match *byte {
+            0x7f => 0x08,
+            0x08 => 0x7f,
+            other => other,
+        };
+    }
+}
+
+/// Checks that `shell` is an existing file, looking it up on `PATH` when it
+/// is not a path.
+fn shell_exists(shell: &str) -> bool {
+    let path = Path::new(shell);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| {
+            let candidate = dir.join(shell);
+            candidate.is_file()
+                || (cfg!(windows) && candidate.with_extension("exe").is_file())
+        })
+    })
+}
+
+#[cfg(not(feature = "tokio"))]
+type EventSender = mpsc::Sender<Event>;
+#[cfg(not(feature = "tokio"))]
+type EventReceiver = mpsc::Receiver<Event>;
+
+#[cfg(feature = "tokio")]
+type EventSender = tokio::sync::mpsc::UnboundedSender<Event>;
+#[cfg(feature = "tokio")]
+type EventReceiver = tokio::sync::mpsc::UnboundedReceiver<Event>;
+
+#[cfg(not(feature = "tokio"))]
+fn event_channel() -> (EventSender, EventReceiver) {
+    mpsc::channel()
+}
+
+#[cfg(feature = "tokio")]
+fn event_channel() -> (EventSender, EventReceiver) {
+    tokio::sync::mpsc::unbounded_channel()
+}
+
+#[cfg(not(feature = "tokio"))]
+fn spawn_pty_event_subscription(
+    id: u64,
+    app_context: egui::Context,
+    event_receiver: EventReceiver,
+    pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
+    pty_writer: Option<Notifier>,
+    options: SubscriptionOptions,
+) -> Result<()> {
+    std::thread::Builder::new()
+        .name(format!("pty_event_subscription_{}", id))
+        .spawn(move || {
+            while let Ok(event) = event_receiver.recv() {
+                if !forward_pty_event(
+                    id,
+                    &app_context,
+                    &pty_event_proxy_sender,
+                    pty_writer.as_ref(),
+                    &options,
+                    event,
+                ) {
+                    break;
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Runs the subscription as a task on the current Tokio runtime instead of
+/// a dedicated thread. Must be called from within a runtime.
+#[cfg(feature = "tokio")]
+fn spawn_pty_event_subscription(
+    id: u64,
+    app_context: egui::Context,
+    mut event_receiver: EventReceiver,
+    pty_event_proxy_sender: Sender<(u64, PtyEvent)>,
+    pty_writer: Option<Notifier>,
+    options: SubscriptionOptions,
+) -> Result<()> {
+    let runtime =
+        tokio::runtime::Handle::try_current().map_err(std::io::Error::other)?;
+    runtime.spawn(async move {
+        while let Some(event) = event_receiver.recv().await {
+            if !forward_pty_event(
+                id,
+                &app_context,
+                &pty_event_proxy_sender,
+                pty_writer.as_ref(),
+                &options,
+                event,
+            ) {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Longest [`TerminalBackend::pump`] waits for the event loop to read the
+/// PTY.
+#[cfg(unix)]
+const PUMP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Bytes written to the PTY by the program that were not read yet.
+#[cfg(unix)]
+fn pending_pty_bytes(fd: RawFd) -> usize {
+    let mut pending: libc::c_int = 0;
+    let result = unsafe { libc::ioctl(fd, libc::FIONREAD, &mut pending) };
+    if result == -1 {
+        return 0;
+    }
+    pending.max(0) as usize
+}
+
+/// The primary Device Attributes response of alacritty, a VT102.
+const PRIMARY_DEVICE_ATTRIBUTES: &str = "\x1b[?6c";
+
+/// How the event subscription reports terminal events to the application.
+#[derive(Debug, Clone)]
+struct SubscriptionOptions {
+    /// Hides the exit, so the application keeps showing the terminal.
+    keep_open_on_exit: bool,
+    /// Requests a repaint of the app for every event.
+    repaint_on_output: bool,
+    /// Writes the responses to terminal queries back to the pty.
+    answer_queries: bool,
+    /// Replaces the primary Device Attributes response.
+    device_attributes: Option<String>,
+}
+
+impl SubscriptionOptions {
+    fn query_response(&self, response: &str) -> String {
+        match &self.device_attributes {
+            Some(attributes) if response == PRIMARY_DEVICE_ATTRIBUTES => {
+                attributes.clone()
+            },
+            _ => response.to_string(),
+        }
+    }
+}
+
+impl Default for SubscriptionOptions {
+    fn default() -> Self {
+        Self {
+            keep_open_on_exit: false,
+            repaint_on_output: true,
+            answer_queries: true,
+            device_attributes: None,
+        }
+    }
+}
+
+impl From<&BackendSettings> for SubscriptionOptions {
+    fn from(settings: &BackendSettings) -> Self {
+        Self {
+            keep_open_on_exit: settings.keep_open_on_exit,
+            repaint_on_output: settings.repaint_on_output,
+            answer_queries: settings.answer_queries,
+            device_attributes: settings.device_attributes.clone(),
+        }
+    }
+}
+
+/// Passes the event to the application and returns `false` once the
+/// subscription should stop.
+fn forward_pty_event(
+    id: u64,
+    app_context: &egui::Context,
+    pty_event_proxy_sender: &Sender<(u64, PtyEvent)>,
+    pty_writer: Option<&Notifier>,
+    options: &SubscriptionOptions,
+    event: Event,
+) -> bool {
+    // Responses to DA, DSR and the other queries of the running program.
+    if let (Event::PtyWrite(response), Some(pty_writer)) = (&event, pty_writer)
+    {
+        if options.answer_queries {
+            pty_writer.notify(options.query_response(response).into_bytes());
+            return true;
+        }
+    }
+
+    let is_exit = matches!(event, Event::Exit);
+    if !(is_exit && options.keep_open_on_exit) {
+        pty_event_proxy_sender
+            .send((id, event))
+            .unwrap_or_else(|_| {
+                panic!(
+                    "pty_event_subscription_{}: sending PtyEvent is failed",
+                    id
+                )
+            });
+    }
+    if options.repaint_on_output {
+        app_context.request_repaint();
+    }
+    !is_exit
+}
+
+/// Copied from alacritty/src/display/hint.rs:
+/// Iterate over all visible regex matches.
+fn visible_regex_match_iter<'a>(
+    term: &'a Term<EventProxy>,
+    regex: &'a mut RegexSearch,
+) -> impl Iterator<Item = Match> + 'a {
+    let viewport_start = Line(-(term.grid().display_offset() as i32));
+    let viewport_end = viewport_start + term.bottommost_line();
+    let mut start =
         term.line_search_left(Point::new(viewport_start, Column(0)));
     let mut end = term.line_search_right(Point::new(viewport_end, Column(0)));
     start.line = start.line.max(viewport_start - 100);
@@ -538,22 +1958,193 @@ fn visible_regex_match_iter<'a>(
         .take_while(move |rm| rm.start().line <= viewport_end)
 }
 
+/// Resizes the grid, rewrapping the scrollback and moving the cursor with
+/// its text. Rewrapping changes how many lines are above the viewport, so
+/// the viewport is kept as many lines back as before, or at the bottom.
+fn resize_grid<T: EventListener>(
+    terminal: &mut Term<T>,
+    cols: u16,
+    lines: u16,
+) {
+    let display_offset = terminal.grid().display_offset() as i32;
+    terminal.resize(TermSize::new(cols as usize, lines as usize));
+    let delta = display_offset - terminal.grid().display_offset() as i32;
+    if delta != 0 {
+        terminal.scroll_display(Scroll::Delta(delta));
+    }
+}
+
+/// Clones only the rows currently shown in the viewport.
+fn viewport_rows(grid: &Grid<Cell>) -> Vec<Vec<Cell>> {
+    let display_offset = grid.display_offset() as i32;
+    (0..grid.screen_lines() as i32)
+        .map(|line| {
+            let row = &grid[Line(line - display_offset)];
+            (0..grid.columns())
+                .map(|column| row[Column(column)].clone())
+                .collect()
+        })
+        .collect()
+}
+
+/// Cursor position in grid coordinates with the cell under it, which
+/// derefs, so `cursor.fg` is the color of the character at the cursor.
+#[derive(Debug, Clone, Default)]
+pub struct Cursor {
+    pub point: Point,
+    pub cell: Cell,
+}
+
+impl Deref for Cursor {
+    type Target = Cell;
+
+    fn deref(&self) -> &Cell {
+        &self.cell
+    }
+}
+
+#[derive(Clone)]
 pub struct RenderableContent {
-    pub grid: Grid<Cell>,
+    /// Visible rows from top to bottom.
+    pub viewport: Vec<Vec<Cell>>,
+    pub display_offset: usize,
     pub hovered_hyperlink: Option<RangeInclusive<Point>>,
     pub selectable_range: Option<SelectionRange>,
-    pub cursor: Cell,
+    pub cursor: Cursor,
+    /// Lines added with [`TerminalBackend::add_mark`], in grid coordinates.
+    pub marks: Vec<Line>,
+    /// Shell integration marks reported with OSC 133, oldest first.
+    pub prompt_marks: Vec<PromptMark>,
+    /// Cursor of copy mode in grid coordinates, while it is on.
+    pub copy_mode_cursor: Option<Point>,
     pub terminal_mode: TermMode,
     pub terminal_size: TerminalSize,
 }
 
+impl RenderableContent {
+    fn new(term: &mut Term<EventProxy>, terminal_size: TerminalSize) -> Self {
+        Self {
+            viewport: viewport_rows(term.grid()),
+            display_offset: term.grid().display_offset(),
+            selectable_range: None,
+            terminal_mode: *term.mode(),
+            terminal_size,
+            cursor: cursor(term),
+            marks: vec![],
+            prompt_marks: vec![],
+            copy_mode_cursor: None,
+            hovered_hyperlink: None,
+        }
+    }
+
+    /// Iterates over the visible cells with their points in grid
+    /// coordinates, like [`Grid::display_iter`].
+    pub fn display_iter(&self) -> impl Iterator<Item = Indexed<&Cell>> {
+        let display_offset = self.display_offset as i32;
+        self.viewport
+            .iter()
+            .enumerate()
+            .flat_map(move |(line, row)| {
+                let line = Line(line as i32 - display_offset);
+                row.iter().enumerate().map(move |(column, cell)| Indexed {
+                    point: Point::new(line, Column(column)),
+                    cell,
+                })
+            })
+    }
+
+    /// Cell layout when the grid is painted at `grid_origin`. The grid
+    /// origin is the widget rect origin unless a fixed size is set.
+    pub fn cell_geometry(&self, grid_origin: Pos2) -> CellGeometry {
+        CellGeometry {
+            origin: grid_origin,
+            cell_size: Vec2::new(
+                self.terminal_size.cell_width as f32,
+                self.terminal_size.cell_height as f32,
+            ),
+            columns: self.terminal_size.num_cols as usize,
+            lines: self.terminal_size.num_lines as usize,
+            display_offset: self.display_offset,
+        }
+    }
+
+    /// Rect of the cell at `point` when the grid is painted at
+    /// `grid_origin`, in the same coordinate space as `TerminalView`.
+    pub fn cell_rect(&self, point: Point, grid_origin: Pos2) -> Rect {
+        self.cell_geometry(grid_origin).cell_rect(point)
+    }
+
+    /// Produces a deterministic textual dump of the visible grid: the
+    /// characters of every line followed by a legend with the colors and
+    /// flags of each cell that differs from the default cell.
+    pub fn to_text_snapshot(&self) -> String {
+        let default_cell = Cell::default();
+        let mut lines: Vec<String> = vec![];
+        let mut legend = String::new();
+        let mut current_line = None;
+        for indexed in self.display_iter() {
+            if current_line != Some(indexed.point.line) {
+                current_line = Some(indexed.point.line);
+                lines.push(String::new());
+            }
+
+            if let Some(line) = lines.last_mut() {
+                line.push(indexed.c);
+            }
+
+            if indexed.fg != default_cell.fg
+                || indexed.bg != default_cell.bg
+                || indexed.flags != default_cell.flags
+            {
+                let _ = writeln!(
+                    legend,
+                    "{}:{} fg={:?} bg={:?} flags={:?}",
+                    indexed.point.line,
+                    indexed.point.column,
+                    indexed.fg,
+                    indexed.bg,
+                    indexed.flags,
+                );
+            }
+        }
+
+        let mut snapshot = format!(
+            "size: {}x{}\ncursor: {}:{}\nmode: {:?}\n",
+            self.viewport.first().map_or(0, |row| row.len()),
+            self.viewport.len(),
+            self.cursor.point.line,
+            self.cursor.point.column,
+            self.terminal_mode,
+        );
+        snapshot.push_str("---\n");
+        for line in lines {
+            snapshot.push_str(line.trim_end());
+            snapshot.push('\n');
+        }
+        snapshot.push_str("---\n");
+        snapshot.push_str(&legend);
+        snapshot
+    }
+}
+
+fn cursor(term: &mut Term<EventProxy>) -> Cursor {
+    Cursor {
+        point: term.grid().cursor.point,
+        cell: term.grid_mut().cursor_cell().clone(),
+    }
+}
+
 impl Default for RenderableContent {
     fn default() -> Self {
         Self {
-            grid: Grid::new(0, 0, 0),
+            viewport: vec![],
+            display_offset: 0,
             hovered_hyperlink: None,
             selectable_range: None,
-            cursor: Cell::default(),
+            cursor: Cursor::default(),
+            marks: vec![],
+            prompt_marks: vec![],
+            copy_mode_cursor: None,
             terminal_mode: TermMode::empty(),
             terminal_size: TerminalSize::default(),
         }
@@ -562,15 +2153,735 @@ impl Default for RenderableContent {
 
 impl Drop for TerminalBackend {
     fn drop(&mut self) {
-        let _ = self.notifier.0.send(Msg::Shutdown);
+        if let Some(notifier) = &self.notifier {
+            let _ = notifier.0.send(Msg::Shutdown);
+        }
     }
 }
 
 #[derive(Clone)]
-pub struct EventProxy(mpsc::Sender<Event>);
+pub struct EventProxy {
+    sender: EventSender,
+    generation: Arc<AtomicU64>,
+    exited: Arc<AtomicBool>,
+    has_output: Arc<AtomicBool>,
+    title: SharedTitle,
+}
+
+type SharedTitle = Arc<Mutex<Option<String>>>;
+
+impl EventProxy {
+    fn new(
+        sender: EventSender,
+        generation: Arc<AtomicU64>,
+        exited: Arc<AtomicBool>,
+        has_output: Arc<AtomicBool>,
+        title: SharedTitle,
+    ) -> Self {
+        Self {
+            sender,
+            generation,
+            exited,
+            has_output,
+            title,
+        }
+    }
+
+    fn set_title(&self, title: Option<String>) {
+        if let Ok(mut current) = self.title.lock() {
+            *current = title;
+        }
+    }
+}
 
 impl EventListener for EventProxy {
     fn send_event(&self, event: Event) {
-        let _ = self.0.send(event.clone());
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        match &event {
+            Event::Exit => self.exited.store(true, Ordering::Relaxed),
+            // Sent by the event loop after it parsed output.
+            Event::Wakeup => self.has_output.store(true, Ordering::Relaxed),
+            // alacritty keeps the title stack and sends the popped title.
+            Event::Title(title) => self.set_title(Some(title.clone())),
+            Event::ResetTitle => self.set_title(None),
+            _ => {},
+        }
+        let _ = self.sender.send(event.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::marks::{MarkId, Marks, ScrollCounter};
+    use super::{
+        event_channel, resize_grid, typed_text, AlacrittySelectionType,
+        AltScreenFilter, BackendCommand, ClipboardRequest, CopyModeMotion,
+        EventProxy, KeyAction, OscParser, PromptMarkKind, RenderableContent,
+        ScrollOnOutput, SharedTitle, ShellOsc, TerminalBackend,
+    };
+    use crate::bindings::{
+        BindingAction, BindingsLayout, InputKind, KeyboardBinding,
+    };
+    use crate::generate_bindings;
+    #[cfg(all(unix, not(feature = "tokio")))]
+    use crate::BackendSettings;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::grid::{Dimensions, Scroll};
+    use alacritty_terminal::index::{Column, Line, Point};
+    use alacritty_terminal::term::cell::{Cell, Flags};
+    use alacritty_terminal::term::{self, test::TermSize, Term, TermMode};
+    use alacritty_terminal::vte::ansi::{self, Color, NamedColor};
+
+    #[test]
+    fn text_snapshot() {
+        let mut viewport = vec![vec![Cell::default(); 3]; 2];
+        viewport[0][0].c = 'l';
+        viewport[0][1].c = 's';
+        viewport[1][0].c = '$';
+        viewport[1][0].fg = Color::Named(NamedColor::Red);
+        viewport[1][0].flags = Flags::BOLD;
+
+        let content = RenderableContent {
+            viewport,
+            ..RenderableContent::default()
+        };
+        let snapshot = content.to_text_snapshot();
+        assert_eq!(
+            snapshot,
+            "size: 3x2\ncursor: 0:0\nmode: TermMode(0x0)\n---\nls\n$\n---\n1:0 fg=Named(Red) bg=Named(Background) flags=Flags(BOLD)\n"
+        );
+        assert_eq!(content.clone().to_text_snapshot(), snapshot);
+    }
+
+    #[test]
+    fn line_selection_spans_soft_wraps() {
+        let mut backend = replay_backend();
+        // Wrapped over the first two rows of the 80 columns.
+        let command = "0123456789".repeat(9);
+        backend.feed_bytes(format!("{}\r\n$ ", command).as_bytes());
+
+        // Cells are one pixel wide and high until the first resize.
+        backend.process_command(BackendCommand::SelectStart(
+            AlacrittySelectionType::Lines,
+            2.5,
+            1.5,
+        ));
+        backend.process_command(BackendCommand::SelectUpdate(2.5, 1.5));
+        assert_eq!(backend.selectable_content().trim_end(), command);
+    }
+
+    fn test_term(
+        cols: usize,
+        lines: usize,
+        input: &[u8],
+    ) -> Term<VoidListener> {
+        let size = TermSize::new(cols, lines);
+        let mut term = Term::new(term::Config::default(), &size, VoidListener);
+        let mut parser: ansi::Processor = ansi::Processor::new();
+        for byte in input {
+            parser.advance(&mut term, *byte);
+        }
+        term
+    }
+
+    /// Text of the cursor row and the cursor column.
+    fn cursor_row(term: &Term<VoidListener>) -> (String, usize) {
+        let cursor = term.grid().cursor.point;
+        let row = &term.grid()[cursor.line];
+        let text: String = (0..term.columns())
+            .map(|column| row[Column(column)].c)
+            .collect();
+        (text.trim_end().to_owned(), cursor.column.0)
+    }
+
+    /// A backend without a PTY, filled by [`TerminalBackend::feed_bytes`].
+    /// With the `tokio` feature, its events are dropped with the runtime.
+    fn replay_backend() -> TerminalBackend {
+        #[cfg(feature = "tokio")]
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        #[cfg(feature = "tokio")]
+        let _guard = runtime.enter();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        TerminalBackend::new_replay(0, egui::Context::default(), sender)
+            .unwrap()
+    }
+
+    #[test]
+    #[cfg(all(unix, not(feature = "tokio")))]
+    fn long_line_is_bounded_by_scrollback() {
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        let settings = BackendSettings {
+            // Prints nothing by itself, so only the fed bytes are shown.
+            shell: String::from("cat"),
+            initial_grid_size: Some((80, 24)),
+            scrollback_lines: 100,
+            ..BackendSettings::default()
+        };
+        let mut backend =
+            TerminalBackend::new(0, egui::Context::default(), sender, settings)
+                .unwrap();
+        backend.feed_bytes(&[b'x'; 1_000_000]);
+
+        // Wrapped at the 80 columns and trimmed to the 100 lines of
+        // scrollback above the 24 of the screen.
+        assert_eq!(backend.scroll_region(), (100, 123, 124));
+    }
+
+    /// A terminal with a scrollback of 10 lines and a screen of 5, and a
+    /// scroll counter kept up to date per line.
+    struct MarkedTerm {
+        term: Term<VoidListener>,
+        parser: ansi::Processor,
+        scroll_counter: ScrollCounter,
+    }
+
+    impl MarkedTerm {
+        fn new() -> Self {
+            let config = term::Config {
+                scrolling_history: 10,
+                ..term::Config::default()
+            };
+            let term = Term::new(config, &TermSize::new(10, 5), VoidListener);
+            Self {
+                term,
+                parser: ansi::Processor::new(),
+                scroll_counter: ScrollCounter::new(10),
+            }
+        }
+
+        fn feed(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.parser.advance(&mut self.term, *byte);
+                if *byte == b'\n' {
+                    self.scroll_counter.update(&mut self.term);
+                }
+            }
+        }
+
+        fn mark(&mut self, marks: &mut Marks) -> MarkId {
+            let line = self.term.grid().cursor.point.line;
+            marks.add(line, self.scroll_counter.update(&mut self.term))
+        }
+
+        fn lines(&mut self, marks: &Marks) -> Vec<(MarkId, String)> {
+            let scrolled = self.scroll_counter.update(&mut self.term);
+            let history_size = self.term.grid().history_size();
+            marks
+                .lines(scrolled, history_size)
+                .into_iter()
+                .map(|(id, line)| {
+                    let row = &self.term.grid()[line];
+                    let text: String = (0..self.term.columns())
+                        .map(|column| row[Column(column)].c)
+                        .collect();
+                    (id, text.trim_end().to_owned())
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn marks_move_up_with_their_lines() {
+        let mut term = MarkedTerm::new();
+        let mut marks = Marks::default();
+        term.feed(b"a\r\nb");
+        let id = term.mark(&mut marks);
+        term.feed(&b"\r\n".repeat(8));
+        assert_eq!(term.lines(&marks), vec![(id, "b".to_owned())]);
+        assert_eq!(
+            marks.line(id, term.scroll_counter.update(&mut term.term), 5),
+            Some(Line(-4))
+        );
+    }
+
+    #[test]
+    fn marks_move_up_with_a_full_scrollback() {
+        let mut term = MarkedTerm::new();
+        let mut marks = Marks::default();
+        for line in 0..30 {
+            term.feed(format!("{}\r\n", line).as_bytes());
+        }
+        term.feed(b"marked");
+        assert_eq!(term.term.grid().history_size(), 10);
+        let id = term.mark(&mut marks);
+        term.feed(b"\r\n1\r\n2\r\n3\r\n4\r\n5\r\n");
+        assert_eq!(term.lines(&marks), vec![(id, "marked".to_owned())]);
+
+        // Past the 10 lines of scrollback the line is gone.
+        term.feed(&b"\r\n".repeat(10));
+        assert!(term.lines(&marks).is_empty());
+    }
+
+    #[test]
+    fn marks_are_dropped_with_the_history() {
+        let mut term = MarkedTerm::new();
+        let mut marks = Marks::default();
+        term.feed(b"history");
+        let in_history = term.mark(&mut marks);
+        term.feed(b"\r\n\r\n\r\n\r\n\r\nscreen");
+        let on_screen = term.mark(&mut marks);
+        assert_eq!(
+            term.lines(&marks),
+            vec![
+                (in_history, "history".to_owned()),
+                (on_screen, "screen".to_owned())
+            ]
+        );
+
+        term.feed(b"\x1b[3J");
+        assert_eq!(term.lines(&marks), vec![(on_screen, "screen".to_owned())]);
+    }
+
+    #[test]
+    fn removed_marks_are_gone() {
+        let mut term = MarkedTerm::new();
+        let mut marks = Marks::default();
+        let first = term.mark(&mut marks);
+        term.feed(b"\r\nsecond");
+        let second = term.mark(&mut marks);
+        marks.remove(first);
+        assert_eq!(term.lines(&marks), vec![(second, "second".to_owned())]);
+
+        marks.clear();
+        assert!(term.lines(&marks).is_empty());
+    }
+
+    #[test]
+    fn resize_keeps_cursor_after_wrapped_line() {
+        let mut term = test_term(10, 4, b"0123456789abcde\r\n$ ");
+        assert_eq!(cursor_row(&term), ("$".to_owned(), 2));
+
+        resize_grid(&mut term, 20, 4);
+        assert_eq!(cursor_row(&term), ("$".to_owned(), 2));
+        resize_grid(&mut term, 5, 4);
+        assert_eq!(cursor_row(&term), ("$".to_owned(), 2));
+    }
+
+    #[test]
+    fn resize_moves_cursor_with_rewrapped_text() {
+        // The text starts below the top row: a line joined back into the
+        // history leaves the cursor clamped to the top row.
+        let mut term = test_term(20, 4, b"$\r\n0123456789abcde");
+        assert_eq!(cursor_row(&term), ("0123456789abcde".to_owned(), 15));
+
+        resize_grid(&mut term, 10, 4);
+        assert_eq!(cursor_row(&term), ("abcde".to_owned(), 5));
+        resize_grid(&mut term, 20, 4);
+        assert_eq!(cursor_row(&term), ("0123456789abcde".to_owned(), 15));
+    }
+
+    #[test]
+    fn resize_keeps_scroll_position() {
+        let input: Vec<u8> = (0..20)
+            .flat_map(|i| format!("{i}\r\n").into_bytes())
+            .collect();
+        let mut term = test_term(10, 4, &input);
+        term.scroll_display(Scroll::Delta(3));
+
+        resize_grid(&mut term, 20, 4);
+        assert_eq!(term.grid().display_offset(), 3);
+        resize_grid(&mut term, 8, 4);
+        assert_eq!(term.grid().display_offset(), 3);
+    }
+
+    #[test]
+    fn osc_133_marks_split_across_reads() {
+        let mut parser = OscParser::default();
+        let mut kinds = vec![];
+        for chunk in
+            [&b"\x1b]133;D;1\x07\x1b]13"[..], b"3;A\x1b\\$ \x1b]2;t\x07"]
+        {
+            kinds.extend(chunk.iter().filter_map(|byte| parser.advance(*byte)));
+        }
+        assert_eq!(
+            kinds,
+            [
+                ShellOsc::PromptMark(PromptMarkKind::CommandFinished {
+                    exit_code: Some(1)
+                }),
+                ShellOsc::PromptMark(PromptMarkKind::PromptStart),
+            ]
+        );
+    }
+
+    /// The master and slave side of a new pty.
+    #[cfg(all(unix, not(feature = "tokio")))]
+    fn open_pty() -> (std::os::fd::OwnedFd, std::os::fd::OwnedFd) {
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        let (mut master, mut slave) = (0, 0);
+        let opened = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(opened, 0);
+        unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) }
+    }
+
+    #[test]
+    #[cfg(all(unix, not(feature = "tokio")))]
+    fn fd_backend_exits_when_the_pty_hangs_up() {
+        use std::time::{Duration, Instant};
+
+        let (master, slave) = open_pty();
+        let program = slave.try_clone().unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        let backend = TerminalBackend::from_raw_fd(
+            0,
+            egui::Context::default(),
+            sender,
+            master,
+            slave,
+        )
+        .unwrap();
+        assert!(!backend.has_exited());
+
+        drop(program);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !backend.has_exited() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(backend.has_exited());
+    }
+
+    #[test]
+    #[cfg(all(unix, not(feature = "tokio")))]
+    fn pump_applies_the_pending_output() {
+        use std::io::Write;
+        use std::time::{Duration, Instant};
+
+        let (master, slave) = open_pty();
+        let mut program = std::fs::File::from(slave.try_clone().unwrap());
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        let mut backend = TerminalBackend::from_raw_fd(
+            0,
+            egui::Context::default(),
+            sender,
+            master,
+            slave,
+        )
+        .unwrap();
+
+        program.write_all(b"hello").unwrap();
+        // The bytes reach the master side of the pty asynchronously.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !backend.pump() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(backend.line_text(Line(0)).trim_end(), "hello");
+        assert!(!backend.pump());
+    }
+
+    #[test]
+    fn osc_7_reports_the_directory() {
+        let mut parser = OscParser::default();
+        let reported: Vec<_> = b"\x1b]7;file://host/tmp/a%20b\x1b\\"
+            .iter()
+            .filter_map(|byte| parser.advance(*byte))
+            .collect();
+        assert_eq!(reported, [ShellOsc::Directory("/tmp/a b".into())]);
+    }
+
+    #[test]
+    fn reported_directory_is_the_current_one() {
+        let mut backend = replay_backend();
+        let directory = std::env::temp_dir();
+        backend.feed_bytes(
+            format!("\x1b]7;file://host{}\x07", directory.display()).as_bytes(),
+        );
+        assert_eq!(backend.current_directory(), Some(directory));
+    }
+
+    #[test]
+    fn popped_title_is_restored() {
+        let (sender, _receiver) = event_channel();
+        let title = SharedTitle::default();
+        let proxy = EventProxy::new(
+            sender,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            title.clone(),
+        );
+        let mut term =
+            Term::new(term::Config::default(), &TermSize::new(10, 2), proxy);
+        let mut parser: ansi::Processor = ansi::Processor::new();
+        let mut current = |bytes: &[u8], term: &mut Term<EventProxy>| {
+            for byte in bytes {
+                parser.advance(term, *byte);
+            }
+            title.lock().unwrap().clone()
+        };
+
+        assert_eq!(
+            current(b"\x1b[22;2t\x1b]2;vim\x07", &mut term).as_deref(),
+            Some("vim")
+        );
+        assert_eq!(current(b"\x1b[23;2t", &mut term), None);
+        assert_eq!(
+            current(b"\x1b]2;shell\x07\x1b[22;2t", &mut term).as_deref(),
+            Some("shell")
+        );
+        assert_eq!(
+            current(b"\x1b]2;top\x07\x1b[23;2t", &mut term).as_deref(),
+            Some("shell")
+        );
+    }
+
+    #[test]
+    fn alt_screen_switches_are_dropped() {
+        let mut filter = AltScreenFilter::default();
+        let mut output = vec![];
+        for chunk in [&b"a\x1b[?10"[..], b"49h\x1b[?25;47l\x1b[?1h\x1b[1mb"] {
+            for byte in chunk {
+                filter.advance(*byte, &mut output);
+            }
+        }
+        assert_eq!(output, b"a\x1b[?25l\x1b[?1h\x1b[1mb");
+    }
+
+    #[test]
+    fn typed_line_breaks_are_sent_like_enter() {
+        let text = "ls\ncd ..\r\nexit\r";
+        assert_eq!(typed_text(text, TermMode::default()), "ls\rcd ..\rexit\r");
+        assert_eq!(
+            typed_text(text, TermMode::LINE_FEED_NEW_LINE),
+            "ls\r\ncd ..\r\nexit\r\n"
+        );
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn pasted_bytes(
+        confirm_multiline_paste: bool,
+        mode: &[u8],
+        text: &str,
+    ) -> (TerminalBackend, std::sync::Arc<std::sync::Mutex<Vec<u8>>>) {
+        use std::sync::{Arc, Mutex};
+
+        let mut backend = replay_backend();
+        backend.confirm_multiline_paste = confirm_multiline_paste;
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let tap_written = written.clone();
+        backend.set_input_tap(Box::new(move |_, bytes| {
+            tap_written.lock().unwrap().extend_from_slice(bytes)
+        }));
+        backend.feed_bytes(mode);
+        backend.paste(text);
+        (backend, written)
+    }
+
+    #[test]
+    #[cfg(not(feature = "tokio"))]
+    fn bracketed_paste_drops_escapes() {
+        let (_, written) =
+            pasted_bytes(false, b"\x1b[?2004h", "a\x1b[20\x1b[201~1~b\x03");
+        assert_eq!(*written.lock().unwrap(), b"\x1b[200~a[20[201~1~b\x1b[201~");
+    }
+
+    #[test]
+    #[cfg(not(feature = "tokio"))]
+    fn multiline_paste_is_held() {
+        let (mut backend, written) = pasted_bytes(true, b"", "ls\nrm -rf x\n");
+        assert!(written.lock().unwrap().is_empty());
+        assert_eq!(backend.pending_paste(), Some("ls\nrm -rf x\n"));
+        backend.confirm_paste();
+        assert_eq!(backend.pending_paste(), None);
+        assert_eq!(*written.lock().unwrap(), b"ls\nrm -rf x\n");
+    }
+
+    #[test]
+    #[cfg(not(feature = "tokio"))]
+    fn plain_paste_is_written_as_is() {
+        let (_, written) = pasted_bytes(false, b"", "echo \x1b[1m\n");
+        assert_eq!(*written.lock().unwrap(), b"echo \x1b[1m\n");
+    }
+
+    #[test]
+    fn sent_keys_resolve_the_given_bindings() {
+        use crate::TerminalMode;
+        use egui::{Key, Modifiers};
+
+        let mut backend = replay_backend();
+        let mut bindings_layout = BindingsLayout::default();
+        bindings_layout.add_bindings(generate_bindings!(
+            KeyboardBinding;
+            F1; BindingAction::Copy;
+            F2; BindingAction::Paste;
+        ));
+        backend.feed_bytes(&b"line\r\n".repeat(100));
+        backend.sync();
+
+        backend.process_command(BackendCommand::SelectAll);
+        let text = backend.selectable_content();
+        assert_eq!(
+            backend.send_key(&bindings_layout, Key::F1, Modifiers::NONE),
+            Some(ClipboardRequest::Copy(text))
+        );
+        assert_eq!(
+            backend.send_key(&bindings_layout, Key::F2, Modifiers::NONE),
+            Some(ClipboardRequest::Paste)
+        );
+        assert_eq!(
+            backend.send_key(&bindings_layout, Key::PageUp, Modifiers::SHIFT),
+            None
+        );
+        assert_eq!(backend.sync().display_offset, 49);
+    }
+
+    #[test]
+    #[cfg(not(feature = "tokio"))]
+    fn exported_state_round_trips() {
+        let mut backend = replay_backend();
+        backend.feed_bytes(
+            "\x1b[1;31mred\x1b[0m 漢字\r\ntwo\r\nthree\x1b[2;3H".as_bytes(),
+        );
+        let state = backend.export_state();
+
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        let restored = TerminalBackend::from_state(
+            1,
+            egui::Context::default(),
+            sender,
+            &state,
+        )
+        .unwrap();
+        assert_eq!(restored.line_text(Line(0)).trim_end(), "red 漢字");
+        assert_eq!(restored.line_text(Line(2)).trim_end(), "three");
+        let term = restored.term.lock();
+        let red = &term.grid()[Line(0)][Column(0)];
+        assert!(red.flags.contains(Flags::BOLD));
+        assert_eq!(red.fg, Color::Named(NamedColor::Red));
+        assert_eq!(
+            term.grid()[Line(0)][Column(3)].fg,
+            Color::Named(NamedColor::Foreground)
+        );
+        assert!(term.grid()[Line(0)][Column(4)]
+            .flags
+            .contains(Flags::WIDE_CHAR));
+        assert_eq!(term.grid()[Line(0)][Column(4)].c, '漢');
+        assert_eq!(term.grid().cursor.point, Point::new(Line(1), Column(2)));
+    }
+
+    #[test]
+    fn restored_cursor_follows_the_text_on_a_smaller_screen() {
+        let mut backend = replay_backend();
+        backend.feed_bytes(b"one\r\ntwo\r\nthree\x1b[2;3H");
+        let state = backend.export_state();
+
+        let mut restored = replay_backend();
+        restored.term.lock().resize(TermSize::new(80, 2));
+        restored.restore_state(&state);
+        assert_eq!(restored.line_text(Line(0)).trim_end(), "two");
+        assert_eq!(
+            restored.term.lock().grid().cursor.point,
+            Point::new(Line(0), Column(2))
+        );
+    }
+
+    #[test]
+    fn unfocused_view_keeps_its_lines() {
+        let mut backend = replay_backend();
+        backend.scroll_on_output = ScrollOnOutput::Focused;
+        backend.feed_bytes(b"first\r\n");
+        backend.set_focused(false);
+        backend.feed_bytes(&b"\r\n".repeat(100));
+        assert_eq!(backend.sync().display_offset, 52);
+        assert_eq!(backend.line_text(Line(-52)).trim_end(), "first");
+
+        backend.set_focused(true);
+        assert_eq!(backend.sync().display_offset, 0);
+    }
+
+    #[test]
+    fn scroll_region_counts_from_oldest_line() {
+        let mut backend = replay_backend();
+        assert_eq!(backend.scroll_region(), (0, 49, 50));
+
+        backend.feed_bytes(&b"\r\n".repeat(100));
+        assert_eq!(backend.scroll_region(), (51, 100, 101));
+        backend.process_command(BackendCommand::Scroll(10));
+        assert_eq!(backend.scroll_region(), (41, 90, 101));
+    }
+
+    #[test]
+    fn copy_mode_selects_with_motions() {
+        let mut backend = replay_backend();
+        backend.feed_bytes(b"hello\r\nworld");
+        for cmd in [
+            BackendCommand::ToggleCopyMode,
+            BackendCommand::CopyModeMotion(CopyModeMotion::Left),
+            BackendCommand::CopyModeSelect,
+            BackendCommand::CopyModeMotion(CopyModeMotion::LineStart),
+        ] {
+            backend.process_command(cmd);
+        }
+        assert_eq!(
+            backend.sync().copy_mode_cursor,
+            Some(Point::new(Line(1), Column(0)))
+        );
+        assert_eq!(backend.selectable_content(), "world");
+
+        backend.process_command(BackendCommand::ToggleCopyMode);
+        assert_eq!(backend.sync().copy_mode_cursor, None);
+        assert!(!backend.has_selection());
+    }
+
+    #[test]
+    fn escape_clears_a_selection_and_is_typed_otherwise() {
+        use egui::{Key, Modifiers};
+
+        let mut backend = replay_backend();
+        let bindings_layout = BindingsLayout::default();
+        backend.feed_bytes(b"hello");
+        backend.process_command(BackendCommand::SelectAll);
+        assert!(backend.has_selection());
+
+        let Some(KeyAction::Command(cmd)) = backend.key_action(
+            &bindings_layout,
+            Key::Escape,
+            Modifiers::NONE,
+            1,
+        ) else {
+            panic!("Escape did not resolve to a command");
+        };
+        assert!(matches!(cmd, BackendCommand::ClearSelection));
+        backend.process_command(cmd);
+        assert!(!backend.has_selection());
+
+        assert!(matches!(
+            backend.key_action(&bindings_layout, Key::Escape, Modifiers::NONE, 1),
+            Some(KeyAction::Command(BackendCommand::Write(bytes)))
+                if bytes == b"\x1b"
+        ));
+    }
+
+    #[test]
+    fn backspace_follows_backspace_sends_delete() {
+        use egui::{Key, Modifiers};
+
+        let mut backend = replay_backend();
+        let bindings_layout = BindingsLayout::default();
+        let typed = |backend: &TerminalBackend, modifiers| match backend
+            .key_action(&bindings_layout, Key::Backspace, modifiers, 1)
+        {
+            Some(KeyAction::Command(BackendCommand::Write(bytes))) => bytes,
+            _ => panic!("Backspace was not typed"),
+        };
+
+        assert_eq!(typed(&backend, Modifiers::NONE), b"\x7f");
+        assert_eq!(typed(&backend, Modifiers::CTRL), b"\x08");
+        assert_eq!(typed(&backend, Modifiers::ALT), b"\x1b\x7f");
+
+        backend.set_backspace_sends_delete(false);
+        assert_eq!(typed(&backend, Modifiers::NONE), b"\x08");
+        assert_eq!(typed(&backend, Modifiers::CTRL), b"\x7f");
+        assert_eq!(typed(&backend, Modifiers::ALT), b"\x1b\x08");
     }
 }
@@ -0,0 +1,364 @@
+use alacritty_terminal::event::{OnResize, WindowSize};
+use alacritty_terminal::tty::{ChildEvent, EventedPty, EventedReadWrite};
+use polling::{Event, PollMode, Poller};
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, OwnedFd};
+
+/// Must match the token alacritty's event loop expects for PTY reads and
+/// writes.
+#[cfg(unix)]
+const PTY_READ_WRITE_TOKEN: usize = 0;
+
+/// Must match the token alacritty's event loop polls child events with.
+#[cfg(unix)]
+const PTY_CHILD_EVENT_TOKEN: usize = 1;
+
+/// Longest private mode sequence held back by the alternate screen
+/// filter, longer ones are passed on unchanged.
+const MAX_PRIVATE_MODE_LEN: usize = 32;
+
+/// Private modes that switch to the alternate screen.
+const ALT_SCREEN_MODES: [u16; 3] = [47, 1047, 1049];
+
+/// Receives a chunk of bytes with the time it was read from or written to
+/// the pty, e.g. to record a session with its timing.
+pub type PtyTap = Box<dyn FnMut(Instant, &[u8]) + Send>;
+
+/// Scans the bytes read from the pty for shell integration sequences.
+type PromptScanner = Box<dyn FnMut(&[u8]) + Send>;
+
+#[derive(Clone, Default)]
+pub(crate) struct SharedPtyTap(Arc<Mutex<Option<PtyTap>>>);
+
+impl SharedPtyTap {
+    pub fn set(&self, tap: PtyTap) {
+        if let Ok(mut current) = self.0.lock() {
+            *current = Some(tap);
+        }
+    }
+
+    pub fn take(&self) -> Option<PtyTap> {
+        self.0.lock().ok().and_then(|mut current| current.take())
+    }
+
+    pub fn call(&self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        if let Ok(mut current) = self.0.lock() {
+            if let Some(tap) = current.as_mut() {
+                tap(Instant::now(), bytes);
+            }
+        }
+    }
+}
+
+/// Wraps a pty and passes every chunk of bytes read from it through the
+/// output tap and the shell integration scanner before the event loop
+/// parses it. With an alternate screen filter, the bytes the event loop
+/// gets are the filtered ones.
+pub(crate) struct TappedPty<P> {
+    pty: P,
+    output_tap: SharedPtyTap,
+    prompt_scanner: PromptScanner,
+    alt_screen_filter: Option<AltScreenFilter>,
+    filtered: Vec<u8>,
+}
+
+impl<P> TappedPty<P> {
+    pub fn new(
+        pty: P,
+        output_tap: SharedPtyTap,
+        prompt_scanner: PromptScanner,
+        alt_screen_filter: Option<AltScreenFilter>,
+    ) -> Self {
+        Self {
+            pty,
+            output_tap,
+            prompt_scanner,
+            alt_screen_filter,
+            filtered: Vec::new(),
+        }
+    }
+}
+
+impl<P: EventedReadWrite> Read for TappedPty<P> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if !self.filtered.is_empty() {
+                let len = self.filtered.len().min(buf.len());
+                buf[..len].copy_from_slice(&self.filtered[..len]);
+                self.filtered.drain(..len);
+                return Ok(len);
+            }
+
+            let len = self.pty.reader().read(buf)?;
+            self.output_tap.call(&buf[..len]);
+            (self.prompt_scanner)(&buf[..len]);
+            let Some(filter) = self.alt_screen_filter.as_mut() else {
+                return Ok(len);
+            };
+            if len == 0 {
+                return Ok(0);
+            }
+
+            // Reads again when the filter holds back all the bytes, as
+            // returning 0 would read as the end of the pty.
+            for byte in &buf[..len] {
+                filter.advance(*byte, &mut self.filtered);
+            }
+        }
+    }
+}
+
+impl<P: EventedReadWrite> EventedReadWrite for TappedPty<P> {
+    type Reader = Self;
+    type Writer = P::Writer;
+
+    unsafe fn register(
+        &mut self,
+        poll: &Arc<Poller>,
+        interest: Event,
+        poll_opts: PollMode,
+    ) -> io::Result<()> {
+        self.pty.register(poll, interest, poll_opts)
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &Arc<Poller>,
+        interest: Event,
+        poll_opts: PollMode,
+    ) -> io::Result<()> {
+        self.pty.reregister(poll, interest, poll_opts)
+    }
+
+    fn deregister(&mut self, poll: &Arc<Poller>) -> io::Result<()> {
+        self.pty.deregister(poll)
+    }
+
+    fn reader(&mut self) -> &mut Self::Reader {
+        self
+    }
+
+    fn writer(&mut self) -> &mut Self::Writer {
+        self.pty.writer()
+    }
+}
+
+impl<P: EventedPty> EventedPty for TappedPty<P> {
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        self.pty.next_child_event()
+    }
+}
+
+impl<P: OnResize> OnResize for TappedPty<P> {
+    fn on_resize(&mut self, window_size: WindowSize) {
+        self.pty.on_resize(window_size);
+    }
+}
+
+/// Drops the private modes that switch to the alternate screen, `?47`,
+/// `?1047` and `?1049`, from the byte stream, so everything stays in the
+/// main screen and its scrollback. Other modes set by the same sequence
+/// are kept.
+#[derive(Debug, Default)]
+pub(crate) struct AltScreenFilter {
+    state: FilterState,
+    held: Vec<u8>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+enum FilterState {
+    #[default]
+    Ground,
+    Escape,
+    Csi,
+    PrivateMode,
+}
+
+impl AltScreenFilter {
+    pub fn advance(&mut self, byte: u8, output: &mut Vec<u8>) {
+        match (&self.state, byte) {
+            (FilterState::Ground, 0x1b) => self.hold(FilterState::Escape, byte),
+            (FilterState::Ground, _) => output.push(byte),
+            (FilterState::Escape, b'[') => self.hold(FilterState::Csi, byte),
+            (FilterState::Csi, b'?') => {
+                self.hold(FilterState::PrivateMode, byte)
+            },
+            (FilterState::PrivateMode, b'0'..=b'9' | b';')
+                if self.held.len() < MAX_PRIVATE_MODE_LEN =>
+            {
+                self.held.push(byte)
+            },
+            (FilterState::PrivateMode, b'h' | b'l') => {
+                self.state = FilterState::Ground;
+                self.write_private_mode(byte, output);
+            },
+            _ => {
+                // Not a mode change, pass it on and start over at the
+                // byte, which may begin the next sequence.
+                self.state = FilterState::Ground;
+                output.append(&mut self.held);
+                self.advance(byte, output);
+            },
+        }
+    }
+
+    fn hold(&mut self, state: FilterState, byte: u8) {
+        self.state = state;
+        self.held.push(byte);
+    }
+
+    fn write_private_mode(&mut self, action: u8, output: &mut Vec<u8>) {
+        let kept: Vec<&[u8]> = self.held[3..]
+            .split(|byte| *byte == b';')
+            .filter(|param| !is_alt_screen_mode(param))
+            .collect();
+        // A sequence with only alternate screen modes is dropped.
+        if !kept.is_empty() {
+            output.extend_from_slice(b"\x1b[?");
+            output.extend_from_slice(&kept.join(&b';'));
+            output.push(action);
+        }
+        self.held.clear();
+    }
+}
+
+fn is_alt_screen_mode(param: &[u8]) -> bool {
+    std::str::from_utf8(param)
+        .ok()
+        .and_then(|param| param.parse().ok())
+        .is_some_and(|mode: u16| ALT_SCREEN_MODES.contains(&mode))
+}
+
+/// A pty opened by the application. No child process is spawned, the
+/// pty reports an exit once it hangs up, when the last program with the
+/// slave side open closes it.
+#[cfg(unix)]
+pub(crate) struct FdPty {
+    master: File,
+    /// Second handle of the master, polled for the hang up.
+    hang_up: File,
+}
+
+#[cfg(unix)]
+impl FdPty {
+    /// The slave is closed here, it stays open in the programs it was
+    /// given to. A kept handle would hide their exit.
+    pub fn new(master: OwnedFd, slave: OwnedFd) -> io::Result<Self> {
+        drop(slave);
+        let master = File::from(master);
+        // The event loop expects non-blocking reads, like the pty opened
+        // by alacritty.
+        unsafe {
+            let fd = master.as_raw_fd();
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags == -1
+                || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK)
+                    == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        let hang_up = master.try_clone()?;
+        Ok(Self { master, hang_up })
+    }
+}
+
+#[cfg(unix)]
+impl EventedReadWrite for FdPty {
+    type Reader = File;
+    type Writer = File;
+
+    unsafe fn register(
+        &mut self,
+        poll: &Arc<Poller>,
+        mut interest: Event,
+        poll_opts: PollMode,
+    ) -> io::Result<()> {
+        interest.key = PTY_READ_WRITE_TOKEN;
+        poll.add_with_mode(&self.master, interest, poll_opts)?;
+        poll.add_with_mode(
+            &self.hang_up,
+            Event::readable(PTY_CHILD_EVENT_TOKEN),
+            PollMode::Level,
+        )
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &Arc<Poller>,
+        mut interest: Event,
+        poll_opts: PollMode,
+    ) -> io::Result<()> {
+        interest.key = PTY_READ_WRITE_TOKEN;
+        poll.modify_with_mode(&self.master, interest, poll_opts)?;
+        poll.modify_with_mode(
+            &self.hang_up,
+            Event::readable(PTY_CHILD_EVENT_TOKEN),
+            PollMode::Level,
+        )
+    }
+
+    fn deregister(&mut self, poll: &Arc<Poller>) -> io::Result<()> {
+        poll.delete(&self.master)?;
+        poll.delete(&self.hang_up)
+    }
+
+    fn reader(&mut self) -> &mut Self::Reader {
+        &mut self.master
+    }
+
+    fn writer(&mut self) -> &mut Self::Writer {
+        &mut self.master
+    }
+}
+
+#[cfg(unix)]
+impl EventedPty for FdPty {
+    /// Polled whenever the master is readable, which it also is once the
+    /// pty hung up.
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        let mut pollfd = libc::pollfd {
+            fd: self.master.as_raw_fd(),
+            events: 0,
+            revents: 0,
+        };
+        let ready = unsafe { libc::poll(&mut pollfd, 1, 0) };
+        (ready == 1 && pollfd.revents & libc::POLLHUP != 0)
+            .then_some(ChildEvent::Exited(None))
+    }
+}
+
+#[cfg(unix)]
+impl OnResize for FdPty {
+    fn on_resize(&mut self, window_size: WindowSize) {
+        let winsize = libc::winsize {
+            ws_row: window_size.num_lines,
+            ws_col: window_size.num_cols,
+            ws_xpixel: window_size
+                .num_cols
+                .saturating_mul(window_size.cell_width),
+            ws_ypixel: window_size
+                .num_lines
+                .saturating_mul(window_size.cell_height),
+        };
+
+        unsafe {
+            libc::ioctl(
+                self.master.as_raw_fd(),
+                libc::TIOCSWINSZ,
+                &winsize as *const _,
+            );
+        }
+    }
+}
@@ -1,14 +1,83 @@
+use std::path::PathBuf;
+
 const DEFAULT_SHELL: &str = "/bin/bash";
+const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
 
 #[derive(Debug, Clone)]
 pub struct BackendSettings {
     pub shell: String,
+    pub working_directory: Option<PathBuf>,
+    /// Backspace sends DEL (`0x7f`) when set and BS (`0x08`) otherwise.
+    /// Ctrl+Backspace sends the other one.
+    pub backspace_sends_delete: bool,
+    /// Keeps the terminal open with its final output when the shell exits,
+    /// instead of reporting `PtyEvent::Exit` to the application.
+    pub keep_open_on_exit: bool,
+    /// Requests a repaint of the app whenever the terminal has new output.
+    /// Disable it to schedule repaints with
+    /// `TerminalBackend::wants_repaint` instead, e.g. to save power.
+    pub repaint_on_output: bool,
+    /// Answers the Device Attributes (DA), Device Status Report (DSR) and
+    /// other queries of the running program by writing the responses back
+    /// to the pty. When disabled the responses are reported to the
+    /// application as `PtyEvent::PtyWrite` instead.
+    pub answer_queries: bool,
+    /// Replaces the primary DA response, `\x1b[?6c` by default, e.g. with
+    /// `\x1b[?62;22c` to advertise a VT220 with color.
+    pub device_attributes: Option<String>,
+    /// Holds back pasted text with newlines, which the shell would run
+    /// line by line, until the app confirms it. See
+    /// `TerminalBackend::pending_paste`. Skipped with bracketed paste.
+    pub confirm_multiline_paste: bool,
+    /// Grid size as `(columns, rows)` the pty is created with, instead of
+    /// 80x50. Set it to the size the view will have, so programs reading
+    /// the size at startup draw for it and the first frame doesn't reflow.
+    pub initial_grid_size: Option<(u16, u16)>,
+    /// Lines kept above the screen, 10 000 by default. Lines longer than
+    /// the grid wrap into as many rows as they need, or overwrite the last
+    /// column when the program turns autowrap off, so a runaway line
+    /// never holds more than this many rows plus the screen.
+    pub scrollback_lines: usize,
+    /// Lets programs switch to the alternate screen (`?1049`, `?1047` and
+    /// `?47`). When disabled the switches are dropped, full screen
+    /// programs draw over the main screen and all output stays in the
+    /// scrollback, e.g. to capture a session linearly.
+    pub allow_alt_screen: bool,
+    /// Which views follow new output while they show the bottom of the
+    /// scrollback.
+    pub scroll_on_output: ScrollOnOutput,
+}
+
+/// How the viewport follows new output. A view scrolled back into the
+/// history always keeps showing the same lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScrollOnOutput {
+    /// Views at the bottom show new output, focused or not.
+    #[default]
+    AtBottom,
+    /// Only the focused view follows new output. An unfocused view keeps
+    /// showing the same lines, e.g. a background pane of a split layout,
+    /// and follows again when it gets focus back if it was at the bottom
+    /// when it lost it. Once the scrollback is full, unfocused views at
+    /// the bottom follow the output as with `AtBottom`.
+    Focused,
 }
 
 impl Default for BackendSettings {
     fn default() -> Self {
         Self {
             shell: DEFAULT_SHELL.to_string(),
+            working_directory: None,
+            backspace_sends_delete: true,
+            keep_open_on_exit: false,
+            repaint_on_output: true,
+            answer_queries: true,
+            device_attributes: None,
+            confirm_multiline_paste: false,
+            initial_grid_size: None,
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            allow_alt_screen: true,
+            scroll_on_output: ScrollOnOutput::default(),
         }
     }
 }
@@ -0,0 +1,146 @@
+use alacritty_terminal::grid::{Dimensions, Row};
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::cell::{Cell, Flags};
+use alacritty_terminal::term::Term;
+use alacritty_terminal::vte::ansi::Color;
+use std::fmt::Write;
+
+/// Cell flags that are stored as SGR attributes.
+const STYLE_FLAGS: Flags = Flags::BOLD
+    .union(Flags::DIM)
+    .union(Flags::ITALIC)
+    .union(Flags::UNDERLINE)
+    .union(Flags::INVERSE)
+    .union(Flags::HIDDEN)
+    .union(Flags::STRIKEOUT);
+
+/// The scrollback, screen and cursor position of a terminal, stored as
+/// text with SGR sequences so it survives changes of the grid size. The
+/// cursor is kept relative to the last row, which stays the bottom of the
+/// text on a screen of another height. Terminal modes and the pending
+/// attributes of the program are not kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerminalStateBlob {
+    bytes: Vec<u8>,
+}
+
+impl TerminalStateBlob {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub(crate) fn export_state<T>(term: &Term<T>) -> TerminalStateBlob {
+    let grid = term.grid();
+    let last_column = grid.columns() - 1;
+    let mut output = String::new();
+    let mut style = None;
+    let cursor = grid.cursor.point;
+    // Blank rows below the text and the cursor are left out, so they don't
+    // push the text into the history of a smaller screen.
+    let last_line = (grid.topmost_line().0..=grid.bottommost_line().0)
+        .rev()
+        .find(|&line| row_end(&grid[Line(line)], last_column) > 0)
+        .map_or(cursor.line.0, |line| line.max(cursor.line.0));
+    for line in grid.topmost_line().0..=last_line {
+        let row = &grid[Line(line)];
+        let is_wrapped =
+            row[Column(last_column)].flags.contains(Flags::WRAPLINE);
+        let end = row_end(row, last_column);
+
+        for column in 0..end {
+            let cell = &row[Column(column)];
+            if cell.flags.intersects(
+                Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER,
+            ) {
+                continue;
+            }
+
+            let cell_style = (cell.fg, cell.bg, cell.flags & STYLE_FLAGS);
+            if style != Some(cell_style) {
+                push_sgr(&mut output, cell);
+                style = Some(cell_style);
+            }
+            output.push(cell.c);
+            if let Some(zerowidth) = cell.zerowidth() {
+                output.extend(zerowidth);
+            }
+        }
+
+        if !is_wrapped && line != last_line {
+            output.push_str("\r\n");
+        }
+    }
+
+    output.push_str("\x1b[0m\r");
+    let rows_up = last_line - cursor.line.0;
+    if rows_up > 0 {
+        let _ = write!(output, "\x1b[{}A", rows_up);
+    }
+    let _ = write!(output, "\x1b[{}G", cursor.column.0 + 1);
+    TerminalStateBlob::from_bytes(output.into_bytes())
+}
+
+/// Column after the last cell of `row` to write. Soft wrapped rows keep
+/// their blanks, so the next row wraps again.
+fn row_end(row: &Row<Cell>, last_column: usize) -> usize {
+    if row[Column(last_column)].flags.contains(Flags::WRAPLINE) {
+        return last_column + 1;
+    }
+
+    (0..last_column + 1)
+        .rposition(|column| !is_blank(&row[Column(column)]))
+        .map_or(0, |column| column + 1)
+}
+
+fn is_blank(cell: &Cell) -> bool {
+    cell.c == ' '
+        && cell.bg == Cell::default().bg
+        && !cell.flags.intersects(STYLE_FLAGS)
+}
+
+fn push_sgr(output: &mut String, cell: &Cell) {
+    output.push_str("\x1b[0");
+    for (flag, code) in [
+        (Flags::BOLD, 1),
+        (Flags::DIM, 2),
+        (Flags::ITALIC, 3),
+        (Flags::UNDERLINE, 4),
+        (Flags::INVERSE, 7),
+        (Flags::HIDDEN, 8),
+        (Flags::STRIKEOUT, 9),
+    ] {
+        if cell.flags.contains(flag) {
+            let _ = write!(output, ";{}", code);
+        }
+    }
+    push_color(output, cell.fg, 30);
+    push_color(output, cell.bg, 40);
+    output.push('m');
+}
+
+/// Foreground colors start at 30 and background colors at 40. The default
+/// colors need no code after the reset.
+fn push_color(output: &mut String, color: Color, base: u8) {
+    let _ = match color {
+        Color::Named(named) => match named as usize {
+            index @ 0..=7 => write!(output, ";{}", base as usize + index),
+            index @ 8..=15 => {
+                write!(output, ";{}", base as usize + 60 + index - 8)
+            },
+            _ => Ok(()),
+        },
+        Color::Indexed(index) => write!(output, ";{};5;{}", base + 8, index),
+        Color::Spec(rgb) => {
+            write!(output, ";{};2;{};{};{}", base + 8, rgb.r, rgb.g, rgb.b)
+        },
+    };
+}
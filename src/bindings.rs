@@ -1,6 +1,11 @@
-use crate::TerminalMode;
+use crate::{CopyModeMotion, TerminalMode};
 use egui::{Key, Modifiers, PointerButton};
 
+/// Added to the terminal mode while a selection exists, so bindings can
+/// depend on one, e.g. Escape clearing it. The terminal never sets it.
+pub const SELECTION_MODE: TerminalMode =
+    TerminalMode::from_bits_retain(1 << 31);
+
 #[derive(Clone, Hash, Debug, PartialEq, Eq)]
 pub enum BindingAction {
     Copy,
@@ -8,6 +13,13 @@ pub enum BindingAction {
     Char(char),
     Esc(String),
     LinkOpen,
+    ClearSelection,
+    SelectAll,
+    ScrollPageUp,
+    ScrollPageDown,
+    ToggleCopyMode,
+    CopyModeMotion(CopyModeMotion),
+    CopyModeSelect,
     Ignore,
 }
 
@@ -25,6 +37,20 @@ pub struct Binding<T> {
     pub terminal_mode_exclude: TerminalMode,
 }
 
+impl<T: PartialEq> Binding<T> {
+    fn is_triggered(
+        &self,
+        input: &T,
+        modifiers: Modifiers,
+        terminal_mode: TerminalMode,
+    ) -> bool {
+        self.target == *input
+            && modifiers.matches_exact(self.modifiers)
+            && terminal_mode.contains(self.terminal_mode_include)
+            && !terminal_mode.intersects(self.terminal_mode_exclude)
+    }
+}
+
 pub type KeyboardBinding = Binding<InputKind>;
 pub type MouseBinding = Binding<InputKind>;
 
@@ -90,9 +116,11 @@ impl Default for BindingsLayout {
 
 impl BindingsLayout {
     pub fn new() -> Self {
+        // Copy mode comes first, so its keys win over the ones typing.
         let mut layout = Self {
-            layout: default_keyboard_bindings(),
+            layout: copy_mode_bindings(),
         };
+        layout.add_bindings(default_keyboard_bindings());
         layout.add_bindings(platform_keyboard_bindings());
         layout.add_bindings(mouse_default_bindings());
         layout
@@ -121,26 +149,76 @@ impl BindingsLayout {
         terminal_mode: TerminalMode,
     ) -> BindingAction {
         for (binding, action) in &self.layout {
-            let is_triggered = binding.target == input
-                && modifiers.matches_exact(binding.modifiers)
-                && terminal_mode.contains(binding.terminal_mode_include)
-                && !terminal_mode.intersects(binding.terminal_mode_exclude);
-
-            if is_triggered {
+            if binding.is_triggered(&input, modifiers, terminal_mode) {
                 return action.clone();
             };
         }
 
         BindingAction::Ignore
     }
+
+    /// Whether any binding triggered by `input` has `action`, also when an
+    /// earlier binding for the same keys wins [`BindingsLayout::get_action`].
+    pub fn has_action(
+        &self,
+        input: InputKind,
+        modifiers: Modifiers,
+        terminal_mode: TerminalMode,
+        action: &BindingAction,
+    ) -> bool {
+        self.layout.iter().any(|(binding, binding_action)| {
+            binding_action == action
+                && binding.is_triggered(&input, modifiers, terminal_mode)
+        })
+    }
+
+    /// Bytes a key press sends to the PTY in `terminal_mode`, or `None`
+    /// when the key is unbound or bound to a non-input action.
+    pub fn key_bytes(
+        &self,
+        key: Key,
+        modifiers: Modifiers,
+        terminal_mode: TerminalMode,
+    ) -> Option<Vec<u8>> {
+        match self.get_action(InputKind::KeyCode(key), modifiers, terminal_mode)
+        {
+            BindingAction::Char(c) => Some(c.to_string().into_bytes()),
+            BindingAction::Esc(seq) => Some(seq.into_bytes()),
+            _ => None,
+        }
+    }
+
+    /// Bytes typed text sends to the PTY. Text that names a bound key is
+    /// skipped, because the key press already produced its bytes.
+    pub fn text_bytes(
+        &self,
+        text: &str,
+        modifiers: Modifiers,
+        terminal_mode: TerminalMode,
+    ) -> Option<Vec<u8>> {
+        match Key::from_name(text) {
+            Some(key)
+                if self.get_action(
+                    InputKind::KeyCode(key),
+                    modifiers,
+                    terminal_mode,
+                ) != BindingAction::Ignore =>
+            {
+                None
+            },
+            _ => Some(text.as_bytes().to_vec()),
+        }
+    }
 }
 
 fn default_keyboard_bindings() -> Vec<(Binding<InputKind>, BindingAction)> {
     generate_bindings!(
         KeyboardBinding;
         // NONE MODIFIERS
-        Enter;     BindingAction::Char('\x0d');
+        Enter,     ~TerminalMode::LINE_FEED_NEW_LINE; BindingAction::Char('\x0d');
+        Enter,     +TerminalMode::LINE_FEED_NEW_LINE; BindingAction::Esc("\x0d\x0a".into());
         Backspace; BindingAction::Char('\x7f');
+        Escape,    +SELECTION_MODE; BindingAction::ClearSelection;
         Escape;    BindingAction::Char('\x1b');
         Tab;       BindingAction::Char('\x09');
         Insert;    BindingAction::Esc("\x1b[2~".into());
@@ -175,8 +253,8 @@ fn default_keyboard_bindings() -> Vec<(Binding<InputKind>, BindingAction)> {
         ArrowLeft,  ~TerminalMode::APP_CURSOR; BindingAction::Esc("\x1b[D".into());
         ArrowRight, ~TerminalMode::APP_CURSOR; BindingAction::Esc("\x1b[C".into());
         // APP_CURSOR Including
-        End,        +TerminalMode::APP_CURSOR; BindingAction::Esc("\x1BOF".into());
-        Home,       +TerminalMode::APP_CURSOR; BindingAction::Esc("\x1BOH".into());
+        End,        +TerminalMode::APP_CURSOR; BindingAction::Esc("\x1bOF".into());
+        Home,       +TerminalMode::APP_CURSOR; BindingAction::Esc("\x1bOH".into());
         ArrowUp,    +TerminalMode::APP_CURSOR; BindingAction::Esc("\x1bOA".into());
         ArrowDown,  +TerminalMode::APP_CURSOR; BindingAction::Esc("\x1bOB".into());
         ArrowLeft,  +TerminalMode::APP_CURSOR; BindingAction::Esc("\x1bOD".into());
@@ -203,6 +281,9 @@ fn default_keyboard_bindings() -> Vec<(Binding<InputKind>, BindingAction)> {
         F10,          Modifiers::CTRL; BindingAction::Esc("\x1b[21;5~".into());
         F11,          Modifiers::CTRL; BindingAction::Esc("\x1b[23;5~".into());
         F12,          Modifiers::CTRL; BindingAction::Esc("\x1b[24;5~".into());
+        Backspace,    Modifiers::CTRL; BindingAction::Char('\x08');
+        Enter,        Modifiers::CTRL, ~TerminalMode::LINE_FEED_NEW_LINE; BindingAction::Char('\x0d');
+        Enter,        Modifiers::CTRL, +TerminalMode::LINE_FEED_NEW_LINE; BindingAction::Esc("\x0d\x0a".into());
         A,            Modifiers::CTRL; BindingAction::Char('\x01');
         B,            Modifiers::CTRL; BindingAction::Char('\x02');
         C,            Modifiers::CTRL; BindingAction::Char('\x03');
@@ -234,13 +315,16 @@ fn default_keyboard_bindings() -> Vec<(Binding<InputKind>, BindingAction)> {
         Backslash,    Modifiers::CTRL; BindingAction::Char('\x1c');
         Minus,        Modifiers::CTRL; BindingAction::Char('\x1f');
         // SHIFT
-        Enter,      Modifiers::SHIFT; BindingAction::Char('\x0d');
+        Enter,      Modifiers::SHIFT, ~TerminalMode::LINE_FEED_NEW_LINE; BindingAction::Char('\x0d');
+        Enter,      Modifiers::SHIFT, +TerminalMode::LINE_FEED_NEW_LINE; BindingAction::Esc("\x0d\x0a".into());
         Backspace,  Modifiers::SHIFT; BindingAction::Char('\x7f');
         Tab,        Modifiers::SHIFT; BindingAction::Esc("\x1b[Z".into());
         End,        Modifiers::SHIFT, +TerminalMode::ALT_SCREEN; BindingAction::Esc("\x1b[1;2F".into());
         Home,       Modifiers::SHIFT, +TerminalMode::ALT_SCREEN; BindingAction::Esc("\x1b[1;2H".into());
         PageUp,     Modifiers::SHIFT, +TerminalMode::ALT_SCREEN; BindingAction::Esc("\x1b[5;2~".into());
         PageDown,   Modifiers::SHIFT, +TerminalMode::ALT_SCREEN; BindingAction::Esc("\x1b[6;2~".into());
+        PageUp,     Modifiers::SHIFT, ~TerminalMode::ALT_SCREEN; BindingAction::ScrollPageUp;
+        PageDown,   Modifiers::SHIFT, ~TerminalMode::ALT_SCREEN; BindingAction::ScrollPageDown;
         ArrowUp,    Modifiers::SHIFT; BindingAction::Esc("\x1b[1;2A".into());
         ArrowDown,  Modifiers::SHIFT; BindingAction::Esc("\x1b[1;2B".into());
         ArrowLeft,  Modifiers::SHIFT; BindingAction::Esc("\x1b[1;2D".into());
@@ -325,6 +409,7 @@ fn platform_keyboard_bindings() -> Vec<(Binding<InputKind>, BindingAction)> {
         KeyboardBinding;
         C, Modifiers::MAC_CMD; BindingAction::Copy;
         V, Modifiers::MAC_CMD; BindingAction::Paste;
+        A, Modifiers::MAC_CMD; BindingAction::SelectAll;
     )
 }
 
@@ -334,6 +419,35 @@ fn platform_keyboard_bindings() -> Vec<(Binding<InputKind>, BindingAction)> {
         KeyboardBinding;
         C, Modifiers::SHIFT | Modifiers::COMMAND; BindingAction::Copy;
         V, Modifiers::SHIFT | Modifiers::COMMAND; BindingAction::Paste;
+        A, Modifiers::SHIFT | Modifiers::COMMAND; BindingAction::SelectAll;
+        Insert, Modifiers::SHIFT; BindingAction::Paste;
+        Insert, Modifiers::CTRL; BindingAction::Copy;
+    )
+}
+
+/// Keys of copy mode, like tmux in vi mode. Enter or `y` copy and leave it.
+fn copy_mode_bindings() -> Vec<(Binding<InputKind>, BindingAction)> {
+    generate_bindings!(
+        KeyboardBinding;
+        Space,      Modifiers::SHIFT | Modifiers::CTRL; BindingAction::ToggleCopyMode;
+        Escape,     +TerminalMode::VI; BindingAction::ToggleCopyMode;
+        Q,          +TerminalMode::VI; BindingAction::ToggleCopyMode;
+        ArrowUp,    +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::Up);
+        ArrowDown,  +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::Down);
+        ArrowLeft,  +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::Left);
+        ArrowRight, +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::Right);
+        K,          +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::Up);
+        J,          +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::Down);
+        H,          +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::Left);
+        L,          +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::Right);
+        Home,       +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::LineStart);
+        End,        +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::LineEnd);
+        B,          +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::WordLeft);
+        W,          +TerminalMode::VI; BindingAction::CopyModeMotion(CopyModeMotion::WordRight);
+        Space,      +TerminalMode::VI; BindingAction::CopyModeSelect;
+        V,          +TerminalMode::VI; BindingAction::CopyModeSelect;
+        Enter,      +TerminalMode::VI; BindingAction::Copy;
+        Y,          +TerminalMode::VI; BindingAction::Copy;
     )
 }
 
@@ -346,9 +460,12 @@ fn mouse_default_bindings() -> Vec<(Binding<InputKind>, BindingAction)> {
 
 #[cfg(test)]
 mod tests {
-    use super::{BindingAction, BindingsLayout, InputKind, KeyboardBinding};
+    use super::{
+        BindingAction, BindingsLayout, InputKind, KeyboardBinding,
+        SELECTION_MODE,
+    };
     use crate::bindings::MouseBinding;
-    use crate::TerminalMode;
+    use crate::{CopyModeMotion, TerminalMode};
     use egui::{Key, Modifiers, PointerButton};
 
     #[test]
@@ -487,4 +604,168 @@ mod tests {
             assert_eq!(action, &found_action);
         }
     }
+
+    #[test]
+    fn get_action_for_cursor_keys_in_app_cursor_mode() {
+        let current_layout = BindingsLayout::default();
+        let cursor_keys = [
+            (Key::ArrowUp, "\x1b[A", "\x1bOA"),
+            (Key::ArrowDown, "\x1b[B", "\x1bOB"),
+            (Key::ArrowRight, "\x1b[C", "\x1bOC"),
+            (Key::ArrowLeft, "\x1b[D", "\x1bOD"),
+            (Key::Home, "\x1b[H", "\x1bOH"),
+            (Key::End, "\x1b[F", "\x1bOF"),
+        ];
+        for (key, normal_seq, app_seq) in cursor_keys {
+            let normal_action = current_layout.get_action(
+                InputKind::KeyCode(key),
+                Modifiers::NONE,
+                TerminalMode::empty(),
+            );
+            assert_eq!(normal_action, BindingAction::Esc(normal_seq.into()));
+
+            let app_action = current_layout.get_action(
+                InputKind::KeyCode(key),
+                Modifiers::NONE,
+                TerminalMode::APP_CURSOR | TerminalMode::ALT_SCREEN,
+            );
+            assert_eq!(app_action, BindingAction::Esc(app_seq.into()));
+        }
+    }
+
+    #[test]
+    fn get_action_for_enter_in_line_feed_new_line_mode() {
+        let current_layout = BindingsLayout::default();
+        for modifiers in [Modifiers::NONE, Modifiers::SHIFT, Modifiers::CTRL] {
+            let normal_action = current_layout.get_action(
+                InputKind::KeyCode(Key::Enter),
+                modifiers,
+                TerminalMode::empty(),
+            );
+            assert_eq!(normal_action, BindingAction::Char('\x0d'));
+
+            let lnm_action = current_layout.get_action(
+                InputKind::KeyCode(Key::Enter),
+                modifiers,
+                TerminalMode::LINE_FEED_NEW_LINE,
+            );
+            assert_eq!(lnm_action, BindingAction::Esc("\x0d\x0a".into()));
+        }
+    }
+
+    #[test]
+    fn key_bytes_follow_terminal_mode() {
+        let current_layout = BindingsLayout::default();
+        assert_eq!(
+            current_layout.key_bytes(
+                Key::ArrowUp,
+                Modifiers::NONE,
+                TerminalMode::empty()
+            ),
+            Some(b"\x1b[A".to_vec())
+        );
+        assert_eq!(
+            current_layout.key_bytes(
+                Key::ArrowUp,
+                Modifiers::NONE,
+                TerminalMode::APP_CURSOR
+            ),
+            Some(b"\x1bOA".to_vec())
+        );
+        assert_eq!(
+            current_layout.key_bytes(
+                Key::C,
+                Modifiers::SHIFT | Modifiers::COMMAND,
+                TerminalMode::empty()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn copy_binding_shares_keys_with_interrupt() {
+        let current_layout = BindingsLayout::default();
+        // What egui reports for Ctrl+Shift+C on Linux and Windows.
+        let modifiers = Modifiers {
+            ctrl: true,
+            shift: true,
+            command: true,
+            ..Modifiers::NONE
+        };
+        assert!(current_layout.has_action(
+            InputKind::KeyCode(Key::C),
+            modifiers,
+            TerminalMode::empty(),
+            &BindingAction::Copy
+        ));
+        assert_eq!(
+            current_layout.key_bytes(Key::C, modifiers, TerminalMode::empty()),
+            Some(b"\x03".to_vec())
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn get_action_for_insert_clipboard_shortcuts() {
+        let current_layout = BindingsLayout::default();
+        assert_eq!(
+            current_layout.get_action(
+                InputKind::KeyCode(Key::Insert),
+                Modifiers::SHIFT,
+                TerminalMode::empty()
+            ),
+            BindingAction::Paste
+        );
+        assert_eq!(
+            current_layout.get_action(
+                InputKind::KeyCode(Key::Insert),
+                Modifiers::CTRL,
+                TerminalMode::empty()
+            ),
+            BindingAction::Copy
+        );
+    }
+
+    #[test]
+    fn copy_mode_keys_move_instead_of_typing() {
+        let current_layout = BindingsLayout::default();
+        assert_eq!(
+            current_layout.get_action(
+                InputKind::KeyCode(Key::ArrowUp),
+                Modifiers::NONE,
+                TerminalMode::VI
+            ),
+            BindingAction::CopyModeMotion(CopyModeMotion::Up)
+        );
+        assert_eq!(
+            current_layout.key_bytes(
+                Key::ArrowUp,
+                Modifiers::NONE,
+                TerminalMode::empty()
+            ),
+            Some(b"\x1b[A".to_vec())
+        );
+    }
+
+    #[test]
+    fn escape_clears_a_selection() {
+        let current_layout = BindingsLayout::default();
+        assert_eq!(
+            current_layout.get_action(
+                InputKind::KeyCode(Key::Escape),
+                Modifiers::NONE,
+                SELECTION_MODE
+            ),
+            BindingAction::ClearSelection
+        );
+        assert_eq!(
+            current_layout.key_bytes(
+                Key::Escape,
+                Modifiers::NONE,
+                TerminalMode::empty()
+            ),
+            Some(b"\x1b".to_vec())
+        );
+    }
 }
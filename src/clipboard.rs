@@ -0,0 +1,8 @@
+/// Clipboard used by `TerminalView` instead of egui's, e.g. backed by
+/// `arboard` on platforms where the egui integration can't read it.
+pub trait ClipboardProvider {
+    /// Text to paste, or None if the clipboard holds no text.
+    fn get_text(&mut self) -> Option<String>;
+
+    fn set_text(&mut self, text: String);
+}
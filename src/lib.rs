@@ -1,13 +1,27 @@
 mod backend;
 mod bindings;
+mod clipboard;
 mod font;
 mod theme;
 mod types;
 mod view;
 
-pub use backend::settings::BackendSettings;
-pub use backend::{PtyEvent, TerminalBackend, TerminalMode, BackendCommand};
-pub use bindings::{Binding, BindingAction, InputKind, KeyboardBinding};
+pub use backend::error::BackendError;
+pub use backend::marks::{MarkId, PromptMark, PromptMarkKind};
+pub use backend::pty::PtyTap;
+pub use backend::settings::{BackendSettings, ScrollOnOutput};
+pub use backend::state::TerminalStateBlob;
+pub use backend::{
+    BackendCommand, ClipboardRequest, CopyModeMotion, Cursor, GridSize,
+    PtyEvent, RenderableContent, ResizeCallback, SearchScope, TerminalBackend,
+    TerminalMode,
+};
+pub use bindings::{
+    Binding, BindingAction, BindingsLayout, InputKind, KeyboardBinding,
+    SELECTION_MODE,
+};
+pub use clipboard::ClipboardProvider;
 pub use font::{FontSettings, TerminalFont};
-pub use theme::{ColorPalette, TerminalTheme};
-pub use view::TerminalView;
+pub use theme::{ColorPalette, ColorPaletteRgb, TerminalTheme};
+pub use types::CellGeometry;
+pub use view::{BackgroundFill, Overlay, TerminalView};
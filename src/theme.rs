@@ -1,6 +1,6 @@
 use alacritty_terminal::vte::ansi::{self, NamedColor};
+use anyhow::Context;
 use egui::Color32;
-use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct ColorPalette {
@@ -69,138 +69,251 @@ impl Default for ColorPalette {
     }
 }
 
+/// A [`ColorPalette`] holding `Color32` values, for palettes generated by
+/// the application, which then need no hex strings that could fail to
+/// parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorPaletteRgb {
+    pub foreground: Color32,
+    pub background: Color32,
+    pub black: Color32,
+    pub red: Color32,
+    pub green: Color32,
+    pub yellow: Color32,
+    pub blue: Color32,
+    pub magenta: Color32,
+    pub cyan: Color32,
+    pub white: Color32,
+    pub bright_black: Color32,
+    pub bright_red: Color32,
+    pub bright_green: Color32,
+    pub bright_yellow: Color32,
+    pub bright_blue: Color32,
+    pub bright_magenta: Color32,
+    pub bright_cyan: Color32,
+    pub bright_white: Color32,
+    pub bright_foreground: Option<Color32>,
+    pub dim_foreground: Color32,
+    pub dim_black: Color32,
+    pub dim_red: Color32,
+    pub dim_green: Color32,
+    pub dim_yellow: Color32,
+    pub dim_blue: Color32,
+    pub dim_magenta: Color32,
+    pub dim_cyan: Color32,
+    pub dim_white: Color32,
+}
+
+impl Default for ColorPaletteRgb {
+    fn default() -> Self {
+        ColorPalette::default().into()
+    }
+}
+
+/// Parses the hex colors of the palette.
+///
+/// # Panics
+///
+/// Panics when a color is not of the form `#rrggbb`.
+impl From<ColorPalette> for ColorPaletteRgb {
+    fn from(palette: ColorPalette) -> Self {
+        Self::try_from(&palette).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+/// Parses the hex colors of the palette, failing on the first one that is
+/// not of the form `#rrggbb`.
+impl TryFrom<&ColorPalette> for ColorPaletteRgb {
+    type Error = anyhow::Error;
+
+    fn try_from(palette: &ColorPalette) -> anyhow::Result<Self> {
+        let parse = |hex: &str| {
+            hex_to_color(hex).with_context(|| format!("invalid color {}", hex))
+        };
+
+        Ok(Self {
+            foreground: parse(&palette.foreground)?,
+            background: parse(&palette.background)?,
+            black: parse(&palette.black)?,
+            red: parse(&palette.red)?,
+            green: parse(&palette.green)?,
+            yellow: parse(&palette.yellow)?,
+            blue: parse(&palette.blue)?,
+            magenta: parse(&palette.magenta)?,
+            cyan: parse(&palette.cyan)?,
+            white: parse(&palette.white)?,
+            bright_black: parse(&palette.bright_black)?,
+            bright_red: parse(&palette.bright_red)?,
+            bright_green: parse(&palette.bright_green)?,
+            bright_yellow: parse(&palette.bright_yellow)?,
+            bright_blue: parse(&palette.bright_blue)?,
+            bright_magenta: parse(&palette.bright_magenta)?,
+            bright_cyan: parse(&palette.bright_cyan)?,
+            bright_white: parse(&palette.bright_white)?,
+            bright_foreground: palette
+                .bright_foreground
+                .as_deref()
+                .map(parse)
+                .transpose()?,
+            dim_foreground: parse(&palette.dim_foreground)?,
+            dim_black: parse(&palette.dim_black)?,
+            dim_red: parse(&palette.dim_red)?,
+            dim_green: parse(&palette.dim_green)?,
+            dim_yellow: parse(&palette.dim_yellow)?,
+            dim_blue: parse(&palette.dim_blue)?,
+            dim_magenta: parse(&palette.dim_magenta)?,
+            dim_cyan: parse(&palette.dim_cyan)?,
+            dim_white: parse(&palette.dim_white)?,
+        })
+    }
+}
+
+impl From<Box<ColorPalette>> for ColorPaletteRgb {
+    fn from(palette: Box<ColorPalette>) -> Self {
+        (*palette).into()
+    }
+}
+
+impl From<Box<ColorPaletteRgb>> for ColorPaletteRgb {
+    fn from(palette: Box<ColorPaletteRgb>) -> Self {
+        *palette
+    }
+}
+
+/// Number of colors addressable by `NamedColor`, which covers the 256
+/// indexed colors followed by the special named ones.
+const COLOR_COUNT: usize = NamedColor::DimForeground as usize + 1;
+
 #[derive(Debug, Clone)]
 pub struct TerminalTheme {
-    palette: Box<ColorPalette>,
-    ansi256_colors: HashMap<u8, Color32>,
+    colors: Box<[Color32; COLOR_COUNT]>,
 }
 
 impl Default for TerminalTheme {
     fn default() -> Self {
-        Self {
-            palette: Box::<ColorPalette>::default(),
-            ansi256_colors: TerminalTheme::get_ansi256_colors(),
-        }
+        Self::new(Box::<ColorPalette>::default())
     }
 }
 
 impl TerminalTheme {
-    pub fn new(palette: Box<ColorPalette>) -> Self {
-        Self {
-            palette,
-            ansi256_colors: TerminalTheme::get_ansi256_colors(),
-        }
-    }
+    /// Builds the theme from a [`ColorPalette`], whose hex colors must be
+    /// valid, or from a [`ColorPaletteRgb`], either of them boxed or not.
+    pub fn new<P: Into<ColorPaletteRgb>>(palette: P) -> Self {
+        let palette: ColorPaletteRgb = palette.into();
+        let mut colors = Box::new([Color32::BLACK; COLOR_COUNT]);
+        let mut set = |name: NamedColor, color: Color32| {
+            colors[name as usize] = color;
+        };
 
-    fn get_ansi256_colors() -> HashMap<u8, Color32> {
-        let mut ansi256_colors = HashMap::new();
+        set(NamedColor::Foreground, palette.foreground);
+        set(NamedColor::Background, palette.background);
+        set(NamedColor::Cursor, palette.background);
+        // Normal terminal colors
+        set(NamedColor::Black, palette.black);
+        set(NamedColor::Red, palette.red);
+        set(NamedColor::Green, palette.green);
+        set(NamedColor::Yellow, palette.yellow);
+        set(NamedColor::Blue, palette.blue);
+        set(NamedColor::Magenta, palette.magenta);
+        set(NamedColor::Cyan, palette.cyan);
+        set(NamedColor::White, palette.white);
+        // Bright terminal colors
+        set(NamedColor::BrightBlack, palette.bright_black);
+        set(NamedColor::BrightRed, palette.bright_red);
+        set(NamedColor::BrightGreen, palette.bright_green);
+        set(NamedColor::BrightYellow, palette.bright_yellow);
+        set(NamedColor::BrightBlue, palette.bright_blue);
+        set(NamedColor::BrightMagenta, palette.bright_magenta);
+        set(NamedColor::BrightCyan, palette.bright_cyan);
+        set(NamedColor::BrightWhite, palette.bright_white);
+        set(
+            NamedColor::BrightForeground,
+            palette.bright_foreground.unwrap_or(palette.foreground),
+        );
+        // Dim terminal colors
+        set(NamedColor::DimForeground, palette.dim_foreground);
+        set(NamedColor::DimBlack, palette.dim_black);
+        set(NamedColor::DimRed, palette.dim_red);
+        set(NamedColor::DimGreen, palette.dim_green);
+        set(NamedColor::DimYellow, palette.dim_yellow);
+        set(NamedColor::DimBlue, palette.dim_blue);
+        set(NamedColor::DimMagenta, palette.dim_magenta);
+        set(NamedColor::DimCyan, palette.dim_cyan);
+        set(NamedColor::DimWhite, palette.dim_white);
 
         for r in 0..6 {
             for g in 0..6 {
                 for b in 0..6 {
                     // Reserve the first 16 colors for config.
                     let index = 16 + r * 36 + g * 6 + b;
-                    let color = Color32::from_rgb(
+                    colors[index as usize] = Color32::from_rgb(
                         if r == 0 { 0 } else { r * 40 + 55 },
                         if g == 0 { 0 } else { g * 40 + 55 },
                         if b == 0 { 0 } else { b * 40 + 55 },
                     );
-                    ansi256_colors.insert(index, color);
                 }
             }
         }
 
-        let index: u8 = 232;
         for i in 0..24 {
             let value = i * 10 + 8;
-            ansi256_colors
-                .insert(index + i, Color32::from_rgb(value, value, value));
+            colors[232 + i as usize] = Color32::from_rgb(value, value, value);
         }
 
-        ansi256_colors
+        Self { colors }
+    }
+
+    /// Like [`TerminalTheme::new`] for a [`ColorPalette`], but returns an
+    /// error instead of panicking when one of its colors is invalid.
+    pub fn try_new(palette: &ColorPalette) -> anyhow::Result<Self> {
+        Ok(Self::new(ColorPaletteRgb::try_from(palette)?))
+    }
+
+    /// Replaces a single indexed or named color. Colors given as RGB are
+    /// not part of the palette and are ignored.
+    pub fn set_color(&mut self, c: ansi::Color, color: Color32) {
+        match c {
+            ansi::Color::Spec(_) => {},
+            ansi::Color::Indexed(index) => self.colors[index as usize] = color,
+            ansi::Color::Named(c) => self.colors[c as usize] = color,
+        }
+    }
+
+    /// All 256 indexed colors, the first 16 taken from the palette.
+    pub fn all_colors(&self) -> [Color32; 256] {
+        std::array::from_fn(|index| {
+            self.get_color(ansi::Color::Indexed(index as u8))
+        })
+    }
+
+    /// The 16 normal and bright colors of the palette with their names.
+    pub fn named_colors(&self) -> [(NamedColor, Color32); 16] {
+        [
+            NamedColor::Black,
+            NamedColor::Red,
+            NamedColor::Green,
+            NamedColor::Yellow,
+            NamedColor::Blue,
+            NamedColor::Magenta,
+            NamedColor::Cyan,
+            NamedColor::White,
+            NamedColor::BrightBlack,
+            NamedColor::BrightRed,
+            NamedColor::BrightGreen,
+            NamedColor::BrightYellow,
+            NamedColor::BrightBlue,
+            NamedColor::BrightMagenta,
+            NamedColor::BrightCyan,
+            NamedColor::BrightWhite,
+        ]
+        .map(|name| (name, self.get_color(ansi::Color::Named(name))))
     }
 
     pub fn get_color(&self, c: ansi::Color) -> Color32 {
         match c {
             ansi::Color::Spec(rgb) => Color32::from_rgb(rgb.r, rgb.g, rgb.b),
-            ansi::Color::Indexed(index) => {
-                if index <= 15 {
-                    let color = match index {
-                        // Normal terminal colors
-                        0 => &self.palette.black,
-                        1 => &self.palette.red,
-                        2 => &self.palette.green,
-                        3 => &self.palette.yellow,
-                        4 => &self.palette.blue,
-                        5 => &self.palette.magenta,
-                        6 => &self.palette.cyan,
-                        7 => &self.palette.white,
-                        // Bright terminal colors
-                        8 => &self.palette.bright_black,
-                        9 => &self.palette.bright_red,
-                        10 => &self.palette.bright_green,
-                        11 => &self.palette.bright_yellow,
-                        12 => &self.palette.bright_blue,
-                        13 => &self.palette.bright_magenta,
-                        14 => &self.palette.bright_cyan,
-                        15 => &self.palette.bright_white,
-                        _ => &self.palette.background,
-                    };
-
-                    return hex_to_color(color)
-                        .unwrap_or_else(|_| panic!("invalid color {}", color));
-                }
-
-                // Other colors
-                match self.ansi256_colors.get(&index) {
-                    Some(color) => *color,
-                    None => Color32::from_rgb(0, 0, 0),
-                }
-            },
-            ansi::Color::Named(c) => {
-                let color = match c {
-                    NamedColor::Foreground => &self.palette.foreground,
-                    NamedColor::Background => &self.palette.background,
-                    // Normal terminal colors
-                    NamedColor::Black => &self.palette.black,
-                    NamedColor::Red => &self.palette.red,
-                    NamedColor::Green => &self.palette.green,
-                    NamedColor::Yellow => &self.palette.yellow,
-                    NamedColor::Blue => &self.palette.blue,
-                    NamedColor::Magenta => &self.palette.magenta,
-                    NamedColor::Cyan => &self.palette.cyan,
-                    NamedColor::White => &self.palette.white,
-                    // Bright terminal colors
-                    NamedColor::BrightBlack => &self.palette.bright_black,
-                    NamedColor::BrightRed => &self.palette.bright_red,
-                    NamedColor::BrightGreen => &self.palette.bright_green,
-                    NamedColor::BrightYellow => &self.palette.bright_yellow,
-                    NamedColor::BrightBlue => &self.palette.bright_blue,
-                    NamedColor::BrightMagenta => &self.palette.bright_magenta,
-                    NamedColor::BrightCyan => &self.palette.bright_cyan,
-                    NamedColor::BrightWhite => &self.palette.bright_white,
-                    NamedColor::BrightForeground => {
-                        match &self.palette.bright_foreground {
-                            Some(color) => color,
-                            None => &self.palette.foreground,
-                        }
-                    },
-                    // Dim terminal colors
-                    NamedColor::DimForeground => &self.palette.dim_foreground,
-                    NamedColor::DimBlack => &self.palette.dim_black,
-                    NamedColor::DimRed => &self.palette.dim_red,
-                    NamedColor::DimGreen => &self.palette.dim_green,
-                    NamedColor::DimYellow => &self.palette.dim_yellow,
-                    NamedColor::DimBlue => &self.palette.dim_blue,
-                    NamedColor::DimMagenta => &self.palette.dim_magenta,
-                    NamedColor::DimCyan => &self.palette.dim_cyan,
-                    NamedColor::DimWhite => &self.palette.dim_white,
-                    _ => &self.palette.background,
-                };
-
-                hex_to_color(color)
-                    .unwrap_or_else(|_| panic!("invalid color {}", color))
-            },
+            ansi::Color::Indexed(index) => self.colors[index as usize],
+            ansi::Color::Named(c) => self.colors[c as usize],
         }
     }
 }
@@ -216,3 +329,127 @@ fn hex_to_color(hex: &str) -> anyhow::Result<Color32> {
 
     Ok(Color32::from_rgb(r, g, b))
 }
+
+/// Moves `fg` towards black or white, whichever contrasts more with `bg`,
+/// until the WCAG contrast ratio between them reaches `minimum_contrast`.
+pub(crate) fn ensure_minimum_contrast(
+    fg: Color32,
+    bg: Color32,
+    minimum_contrast: f32,
+) -> Color32 {
+    if minimum_contrast <= 1.0 || contrast_ratio(fg, bg) >= minimum_contrast {
+        return fg;
+    }
+
+    let target = if contrast_ratio(Color32::WHITE, bg)
+        >= contrast_ratio(Color32::BLACK, bg)
+    {
+        Color32::WHITE
+    } else {
+        Color32::BLACK
+    };
+
+    if contrast_ratio(target, bg) < minimum_contrast {
+        return target;
+    }
+
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..8 {
+        let middle = (low + high) / 2.0;
+        if contrast_ratio(fg.lerp_to_gamma(target, middle), bg)
+            >= minimum_contrast
+        {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+
+    fg.lerp_to_gamma(target, high)
+}
+
+fn contrast_ratio(a: Color32, b: Color32) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+fn relative_luminance(color: Color32) -> f32 {
+    let channel = |value: u8| {
+        let value = value as f32 / 255.0;
+        if value <= 0.03928 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    0.2126 * channel(color.r())
+        + 0.7152 * channel(color.g())
+        + 0.0722 * channel(color.b())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        contrast_ratio, ensure_minimum_contrast, ColorPalette, TerminalTheme,
+    };
+    use alacritty_terminal::vte::ansi::{self, NamedColor};
+    use egui::Color32;
+
+    #[test]
+    fn palette_colors_are_looked_up_by_name_and_index() {
+        let palette = ColorPalette {
+            red: String::from("#102030"),
+            bright_foreground: None,
+            ..Default::default()
+        };
+        let mut theme = TerminalTheme::try_new(&palette).unwrap();
+        let red = Color32::from_rgb(0x10, 0x20, 0x30);
+
+        assert_eq!(theme.get_color(ansi::Color::Named(NamedColor::Red)), red);
+        assert_eq!(theme.get_color(ansi::Color::Indexed(1)), red);
+        assert_eq!(
+            theme.get_color(ansi::Color::Named(NamedColor::BrightForeground)),
+            theme.get_color(ansi::Color::Named(NamedColor::Foreground)),
+        );
+        assert_eq!(
+            theme.get_color(ansi::Color::Indexed(16 + 36 + 6 + 1)),
+            Color32::from_rgb(95, 95, 95),
+        );
+        assert_eq!(
+            theme.get_color(ansi::Color::Indexed(232)),
+            Color32::from_rgb(8, 8, 8),
+        );
+
+        theme.set_color(ansi::Color::Indexed(1), Color32::RED);
+        assert_eq!(
+            theme.get_color(ansi::Color::Named(NamedColor::Red)),
+            Color32::RED,
+        );
+    }
+
+    #[test]
+    fn invalid_palette_color_is_an_error() {
+        let palette = ColorPalette {
+            green: String::from("green"),
+            ..Default::default()
+        };
+        let err = TerminalTheme::try_new(&palette).unwrap_err();
+        assert_eq!(err.to_string(), "invalid color green");
+    }
+
+    #[test]
+    fn foreground_is_adjusted_to_the_minimum_contrast() {
+        let bg = Color32::from_rgb(40, 40, 40);
+        let fg = Color32::from_rgb(60, 60, 60);
+        assert_eq!(ensure_minimum_contrast(fg, bg, 1.0), fg);
+
+        let adjusted = ensure_minimum_contrast(fg, bg, 4.5);
+        assert!(contrast_ratio(adjusted, bg) >= 4.5);
+        // Lightened towards white, which contrasts more with the dark bg.
+        assert!(adjusted.r() > fg.r());
+
+        let readable = Color32::from_rgb(230, 230, 230);
+        assert_eq!(ensure_minimum_contrast(readable, bg, 4.5), readable);
+    }
+}
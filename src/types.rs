@@ -1,4 +1,5 @@
-use egui::Vec2;
+use alacritty_terminal::index::Point;
+use egui::{Pos2, Rect, Vec2};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Size {
@@ -20,3 +21,29 @@ impl From<Vec2> for Size {
         }
     }
 }
+
+/// Layout of the grid cells on screen, in the same coordinate space the
+/// terminal is painted in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellGeometry {
+    pub origin: Pos2,
+    pub cell_size: Vec2,
+    pub columns: usize,
+    pub lines: usize,
+    pub display_offset: usize,
+}
+
+impl CellGeometry {
+    /// Rect of the cell at `point`, where the point is in grid coordinates
+    /// (lines in the scrollback are negative).
+    pub fn cell_rect(&self, point: Point) -> Rect {
+        let line = point.line.0.saturating_add(self.display_offset as i32);
+        Rect::from_min_size(
+            Pos2::new(
+                self.origin.x + point.column.0 as f32 * self.cell_size.x,
+                self.origin.y + line as f32 * self.cell_size.y,
+            ),
+            self.cell_size,
+        )
+    }
+}
@@ -1,36 +1,75 @@
-use alacritty_terminal::index::Point as TerminalGridPoint;
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Column, Line, Point as TerminalGridPoint};
 use alacritty_terminal::term::cell;
 use alacritty_terminal::term::TermMode;
+use alacritty_terminal::vte::ansi::{self, NamedColor};
+use egui::text::{LayoutJob, TextFormat};
 use egui::Key;
 use egui::Modifiers;
 use egui::MouseWheelUnit;
 use egui::Widget;
-use egui::{Align2, Painter, Pos2, Rect, Response, Rounding, Stroke, Vec2};
-use egui::{Id, PointerButton};
+use egui::{
+    Align2, Color32, FontId, Mesh, Painter, Pos2, Rect, Response, Rounding,
+    Shape, Stroke, TextureId, Vec2,
+};
+use egui::{
+    CursorIcon, EventFilter, Id, PointerButton, WidgetInfo, WidgetType,
+};
 
 use crate::backend::BackendCommand;
+use crate::backend::RenderableContent;
 use crate::backend::TerminalBackend;
+use crate::backend::{ClipboardRequest, KeyAction};
 use crate::backend::{LinkAction, MouseButton, SelectionType};
 use crate::bindings::Binding;
 use crate::bindings::{BindingAction, BindingsLayout, InputKind};
+use crate::clipboard::ClipboardProvider;
 use crate::font::TerminalFont;
-use crate::theme::TerminalTheme;
-use crate::types::Size;
+use crate::theme::{ensure_minimum_contrast, TerminalTheme};
+use crate::types::{CellGeometry, Size};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
 const EGUI_TERM_WIDGET_ID_PREFIX: &str = "egui_term::instance::";
+const DEFAULT_MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(300);
+const DEFAULT_MULTI_CLICK_DISTANCE: f32 = 6.0;
+/// Above this many visible cells, cell backgrounds are merged into runs so
+/// the frame does not hold a rect per cell.
+const MAX_UNBATCHED_CELLS: usize = 20_000;
 
 #[derive(Debug, Clone)]
 enum InputAction {
     BackendCall(BackendCommand),
     WriteToClipboard(String),
+    RequestPaste,
     Ignore,
 }
 
+/// What is painted behind the cells instead of the theme background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundFill {
+    Solid(Color32),
+    /// A vertical gradient from the top color to the bottom color.
+    Gradient(Color32, Color32),
+    /// An image stretched over the widget.
+    Texture(TextureId),
+}
+
+/// Paints custom shapes on top of the terminal, see
+/// [`TerminalView::set_overlay`].
+pub type Overlay<'a> = Box<dyn Fn(&Painter, &CellGeometry) + 'a>;
+
 #[derive(Clone, Default, Debug)]
 pub struct TerminalViewState {
     is_dragged: bool,
+    click_count: u8,
+    last_click_time: f64,
+    last_click_position: Pos2,
+    last_input_time: f64,
     scroll_pixels: f32,
     current_mouse_position_on_grid: TerminalGridPoint,
+    is_paste_requested: bool,
 }
 
 pub struct TerminalView<'a> {
@@ -40,13 +79,52 @@ pub struct TerminalView<'a> {
     backend: &'a mut TerminalBackend,
     font: TerminalFont,
     theme: TerminalTheme,
+    light_theme: Option<TerminalTheme>,
     bindings_layout: BindingsLayout,
+    minimum_contrast: f32,
+    inactive_tint: Option<Color32>,
+    fixed_size: Option<(u16, u16)>,
+    min_grid_size: Option<(u16, u16)>,
+    read_only: bool,
+    draw_trailing_bg: bool,
+    render_whitespace: bool,
+    selection_colors: Option<(Color32, Color32)>,
+    context_menu: bool,
+    mouse_reporting: bool,
+    multi_click_interval: Duration,
+    multi_click_distance: f32,
+    cursor_blink_interval: Option<Duration>,
+    reduce_motion: bool,
+    bold_is_bright: bool,
+    status_line: Option<String>,
+    status_line_color: Option<Color32>,
+    scroll_requires_focus: bool,
+    crisp_text: bool,
+    rounding: Rounding,
+    scroll_page_lines: Option<u16>,
+    snap_to_cell: bool,
+    capture_tab: bool,
+    startup_placeholder: Option<String>,
+    show_scroll_boundary: bool,
+    background_fill: Option<BackgroundFill>,
+    overlay: Option<Overlay<'a>>,
+    clipboard: Option<Box<dyn ClipboardProvider + 'a>>,
+    highlights: Vec<(RangeInclusive<TerminalGridPoint>, Color32)>,
 }
 
 impl Widget for TerminalView<'_> {
-    fn ui(self, ui: &mut egui::Ui) -> Response {
-        let (layout, painter) =
-            ui.allocate_painter(self.size, egui::Sense::click());
+    fn ui(mut self, ui: &mut egui::Ui) -> Response {
+        if let Some(light_theme) = self.light_theme.take() {
+            if !ui.visuals().dark_mode {
+                self.theme = light_theme;
+            }
+        }
+
+        let mut size = self.size.max(self.min_size(ui.ctx()));
+        if self.snap_to_cell {
+            size.x = self.snap_width(size.x, ui.ctx());
+        }
+        let (layout, painter) = ui.allocate_painter(size, egui::Sense::click());
 
         let widget_id = self.widget_id;
         let mut state = ui.memory(|m| {
@@ -58,6 +136,8 @@ impl Widget for TerminalView<'_> {
         self.focus(&layout)
             .resize(&layout)
             .process_input(&layout, &mut state)
+            .show_context_menu(&layout, &mut state)
+            .update_cursor_icon(&layout, &state)
             .show(&mut state, &layout, &painter);
 
         ui.memory_mut(|m| m.data.insert_temp(widget_id, state));
@@ -79,13 +159,57 @@ impl<'a> TerminalView<'a> {
             backend,
             font: TerminalFont::default(),
             theme: TerminalTheme::default(),
+            light_theme: None,
             bindings_layout: BindingsLayout::new(),
+            minimum_contrast: 1.0,
+            inactive_tint: None,
+            fixed_size: None,
+            min_grid_size: None,
+            read_only: false,
+            draw_trailing_bg: true,
+            render_whitespace: false,
+            selection_colors: None,
+            context_menu: true,
+            mouse_reporting: true,
+            multi_click_interval: DEFAULT_MULTI_CLICK_INTERVAL,
+            multi_click_distance: DEFAULT_MULTI_CLICK_DISTANCE,
+            cursor_blink_interval: None,
+            reduce_motion: false,
+            bold_is_bright: false,
+            status_line: None,
+            status_line_color: None,
+            scroll_requires_focus: true,
+            crisp_text: false,
+            rounding: Rounding::ZERO,
+            scroll_page_lines: None,
+            snap_to_cell: false,
+            capture_tab: true,
+            startup_placeholder: None,
+            show_scroll_boundary: false,
+            background_fill: None,
+            overlay: None,
+            clipboard: None,
+            highlights: Vec::new(),
         }
     }
 
     #[inline]
     pub fn set_theme(mut self, theme: TerminalTheme) -> Self {
         self.theme = theme;
+        self.light_theme = None;
+        self
+    }
+
+    /// Uses `light` or `dark` depending on whether the egui visuals are in
+    /// dark mode, so the terminal follows the rest of the UI.
+    #[inline]
+    pub fn set_theme_auto(
+        mut self,
+        light: TerminalTheme,
+        dark: TerminalTheme,
+    ) -> Self {
+        self.theme = dark;
+        self.light_theme = Some(light);
         self
     }
 
@@ -107,6 +231,275 @@ impl<'a> TerminalView<'a> {
         self
     }
 
+    /// Sets the minimum WCAG contrast ratio between the text and its
+    /// background. The foreground is adjusted until it is reached.
+    /// `1.0` (the default) leaves colors untouched.
+    #[inline]
+    pub fn set_minimum_contrast(mut self, minimum_contrast: f32) -> Self {
+        self.minimum_contrast = minimum_contrast;
+        self
+    }
+
+    /// Sets a translucent color painted over the whole terminal while the
+    /// widget doesn't have focus.
+    #[inline]
+    pub fn set_inactive_tint(mut self, tint: Option<Color32>) -> Self {
+        self.inactive_tint = tint;
+        self
+    }
+
+    /// Keeps the grid at exactly `cols` x `rows` cells regardless of the
+    /// widget size. The grid is centered and the margins are filled with
+    /// the background color. The widget grows to fit the grid if needed.
+    #[inline]
+    pub fn set_fixed_size(mut self, cols: u16, rows: u16) -> Self {
+        self.fixed_size = Some((cols, rows));
+        self
+    }
+
+    /// Makes the widget at least large enough for `cols` x `rows` cells, so
+    /// it doesn't collapse in scroll areas and resizable panels.
+    #[inline]
+    pub fn set_min_grid_size(mut self, cols: u16, rows: u16) -> Self {
+        self.min_grid_size = Some((cols, rows));
+        self
+    }
+
+    /// Drops everything that would be sent to the PTY (typing, pasting and
+    /// mouse reports) while keeping scrolling, selection and copying.
+    #[inline]
+    pub fn set_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// When disabled, blank cells after the last character of a line are
+    /// drawn with the terminal background instead of their own color.
+    #[inline]
+    pub fn set_draw_trailing_bg(mut self, draw_trailing_bg: bool) -> Self {
+        self.draw_trailing_bg = draw_trailing_bg;
+        self
+    }
+
+    /// Draws spaces as `·` and tabs as `→` in a faint foreground color, up
+    /// to the end of the written text of each line. Trailing spaces count
+    /// when they are before the cursor, colored or on a soft wrapped line,
+    /// others can't be told apart from cells that were never written.
+    #[inline]
+    pub fn set_render_whitespace(mut self, render_whitespace: bool) -> Self {
+        self.render_whitespace = render_whitespace;
+        self
+    }
+
+    /// Draws selected cells with these colors instead of swapping their
+    /// foreground and background.
+    #[inline]
+    pub fn set_selection_colors(mut self, fg: Color32, bg: Color32) -> Self {
+        self.selection_colors = Some((fg, bg));
+        self
+    }
+
+    /// When disabled, the mouse always selects text locally, even if the
+    /// program enabled mouse reporting. Holding Shift does the same for a
+    /// single click or drag.
+    #[inline]
+    pub fn set_mouse_reporting_enabled(mut self, enabled: bool) -> Self {
+        self.mouse_reporting = enabled;
+        self
+    }
+
+    /// Longest time between clicks that still counts as a double or triple
+    /// click, selecting words or lines.
+    #[inline]
+    pub fn set_multi_click_interval(mut self, interval: Duration) -> Self {
+        self.multi_click_interval = interval;
+        self
+    }
+
+    /// Farthest distance in points between clicks that still counts as a
+    /// double or triple click.
+    #[inline]
+    pub fn set_multi_click_distance(mut self, distance: f32) -> Self {
+        self.multi_click_distance = distance;
+        self
+    }
+
+    /// Shows a menu with Copy, Paste, Select All and Clear on right click.
+    /// Disable it to provide a custom menu.
+    #[inline]
+    pub fn set_context_menu(mut self, context_menu: bool) -> Self {
+        self.context_menu = context_menu;
+        self
+    }
+
+    /// Makes the cursor blink with the given interval. The cursor stays
+    /// solid for one interval after keyboard input.
+    #[inline]
+    pub fn set_cursor_blink_interval(mut self, interval: Duration) -> Self {
+        self.cursor_blink_interval = Some(interval);
+        self
+    }
+
+    /// When disabled, the mouse wheel scrolls a hovered terminal without
+    /// focus, e.g. to read another pane. Other input still needs focus.
+    #[inline]
+    pub fn set_scroll_requires_focus(
+        mut self,
+        scroll_requires_focus: bool,
+    ) -> Self {
+        self.scroll_requires_focus = scroll_requires_focus;
+        self
+    }
+
+    /// Lines Shift+PageUp and Shift+PageDown scroll by. `None` scrolls by
+    /// the screen height minus one line, which stays visible for context.
+    #[inline]
+    pub fn set_scroll_page_lines(mut self, lines: Option<u16>) -> Self {
+        self.scroll_page_lines = lines;
+        self
+    }
+
+    /// Rounds the corners of the terminal background, e.g. to match the
+    /// surrounding panels. The grid is inset so no cell reaches into the
+    /// rounded corners.
+    #[inline]
+    pub fn set_rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Draws every glyph on its own at a whole physical pixel, instead of
+    /// laying out runs whose glyphs may land between pixels. Small fonts on
+    /// low DPI screens look sharper at the cost of more shapes per frame.
+    #[inline]
+    pub fn set_crisp_text(mut self, crisp_text: bool) -> Self {
+        self.crisp_text = crisp_text;
+        self
+    }
+
+    /// Reserves the bottom row of the widget for the given text, e.g. the
+    /// grid size or working directory. The grid shrinks by one line.
+    #[inline]
+    pub fn set_status_line(mut self, status_line: Option<String>) -> Self {
+        self.status_line = status_line;
+        self
+    }
+
+    /// Color of the status line text, the theme foreground by default.
+    #[inline]
+    pub fn set_status_line_color(mut self, color: Color32) -> Self {
+        self.status_line_color = Some(color);
+        self
+    }
+
+    /// Draws bold text with the bright variant of the eight basic colors,
+    /// as many color schemes expect.
+    #[inline]
+    pub fn set_bold_is_bright(mut self, bold_is_bright: bool) -> Self {
+        self.bold_is_bright = bold_is_bright;
+        self
+    }
+
+    /// Turns off every animation of the view for motion sensitive users.
+    /// The cursor stays solid even with a blink interval set. The view has
+    /// no bell flash and scrolls whole lines without easing, so there is
+    /// nothing else to turn off.
+    #[inline]
+    pub fn set_reduce_motion(mut self, reduce_motion: bool) -> Self {
+        self.reduce_motion = reduce_motion;
+        self
+    }
+
+    /// Sets a callback that paints custom shapes on top of the terminal,
+    /// using the cell geometry to align them to the grid.
+    #[inline]
+    pub fn set_overlay(mut self, overlay: Overlay<'a>) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Paints `fill` behind the cells. Cells in the theme background color
+    /// leave it visible, cells with another background still cover it.
+    /// Gradients and textures ignore the rounding of the corners.
+    #[inline]
+    pub fn set_background_fill(mut self, fill: BackgroundFill) -> Self {
+        self.background_fill = Some(fill);
+        self
+    }
+
+    /// Text drawn centered, e.g. "Connecting…", until the program prints
+    /// its first output.
+    #[inline]
+    pub fn set_startup_placeholder(
+        mut self,
+        placeholder: Option<String>,
+    ) -> Self {
+        self.startup_placeholder = placeholder;
+        self
+    }
+
+    /// While scrolled back, draws a line where the live screen starts, or a
+    /// shadow along the bottom edge when it is below the viewport. Off by
+    /// default.
+    #[inline]
+    pub fn set_show_scroll_boundary(mut self, show: bool) -> Self {
+        self.show_scroll_boundary = show;
+        self
+    }
+
+    /// Whether Tab and Shift+Tab are sent to the terminal while it has
+    /// focus. Disable it to let them move focus to the next widget. On by
+    /// default.
+    #[inline]
+    pub fn set_capture_tab(mut self, capture_tab: bool) -> Self {
+        self.capture_tab = capture_tab;
+        self
+    }
+
+    /// Shrinks the widget to a whole number of columns, see
+    /// [`TerminalView::snap_width`]. Off by default.
+    #[inline]
+    pub fn set_snap_to_cell(mut self, snap_to_cell: bool) -> Self {
+        self.snap_to_cell = snap_to_cell;
+        self
+    }
+
+    /// The largest multiple of the cell width that fits in `available`,
+    /// e.g. to size a resizable panel so no partial column is left at the
+    /// right edge.
+    pub fn snap_width(&self, available: f32, ctx: &egui::Context) -> f32 {
+        let cell_width = self.font.font_measure(ctx).width.floor();
+        if cell_width <= 0.0 {
+            return available;
+        }
+
+        (available / cell_width).floor() * cell_width
+    }
+
+    /// Copies and pastes through `clipboard` instead of egui. Pastes are
+    /// read right away instead of waiting for the paste event of the
+    /// integration. Paste events egui still sends are pasted as before.
+    #[inline]
+    pub fn set_clipboard(
+        mut self,
+        clipboard: Box<dyn ClipboardProvider + 'a>,
+    ) -> Self {
+        self.clipboard = Some(clipboard);
+        self
+    }
+
+    /// Paints the background of each range with its color, under the
+    /// selection. Ranges are in grid coordinates and later ranges win
+    /// where they overlap.
+    #[inline]
+    pub fn set_highlights(
+        mut self,
+        highlights: Vec<(RangeInclusive<TerminalGridPoint>, Color32)>,
+    ) -> Self {
+        self.highlights = highlights;
+        self
+    }
+
     #[inline]
     pub fn add_bindings(
         mut self,
@@ -119,55 +512,291 @@ impl<'a> TerminalView<'a> {
     fn focus(self, layout: &Response) -> Self {
         if self.has_focus {
             layout.request_focus();
+            // Keeps egui from moving focus on these keys, so they reach the
+            // shell, e.g. Tab for completion.
+            layout.ctx.memory_mut(|m| {
+                m.set_focus_lock_filter(
+                    layout.id,
+                    EventFilter {
+                        tab: self.capture_tab,
+                        horizontal_arrows: true,
+                        vertical_arrows: true,
+                        escape: true,
+                    },
+                )
+            });
         } else {
             layout.surrender_focus();
         }
+        self.backend.set_focused(layout.has_focus());
 
         self
     }
 
     fn resize(self, layout: &Response) -> Self {
+        let font_size = self.font.font_measure(&layout.ctx);
+        let mut grid_size = self.grid_rect(layout).size();
+        if self.fixed_size.is_none() {
+            // Rows are a whole number of pixels apart while glyphs take the
+            // full row height, so the last row needs the fraction below it.
+            grid_size.y -= font_size.height.fract();
+        }
         self.backend.process_command(BackendCommand::Resize(
-            Size::from(layout.rect.size()),
-            self.font.font_measure(&layout.ctx),
+            Size::from(grid_size),
+            font_size,
         ));
 
         self
     }
 
+    /// Returns whether a blinking cursor is in its visible phase and
+    /// schedules a repaint for the next toggle. The phase restarts with
+    /// every keyboard input and blinking pauses without focus.
+    fn cursor_blink_phase(
+        &self,
+        state: &TerminalViewState,
+        layout: &Response,
+    ) -> bool {
+        // An unfocused terminal draws a static cursor instead of
+        // repainting for every blink.
+        if self.reduce_motion || !layout.has_focus() {
+            return true;
+        }
+
+        let Some(interval) = self.cursor_blink_interval else {
+            return true;
+        };
+
+        let interval = interval.as_secs_f64();
+        if interval <= 0.0 {
+            return true;
+        }
+
+        let elapsed = layout.ctx.input(|i| i.time) - state.last_input_time;
+        let phase = (elapsed / interval).max(0.0);
+        layout.ctx.request_repaint_after(Duration::from_secs_f64(
+            (phase.floor() + 1.0 - phase) * interval,
+        ));
+        (phase as u64).is_multiple_of(2)
+    }
+
+    /// The pixel size of the fixed or minimum grid, whichever is larger,
+    /// zero when neither is set.
+    fn min_size(&self, ctx: &egui::Context) -> Vec2 {
+        let font_size = self.font.font_measure(ctx);
+        [self.fixed_size, self.min_grid_size]
+            .into_iter()
+            .flatten()
+            .map(|(cols, rows)| {
+                let rows = rows + u16::from(self.status_line.is_some());
+                Vec2::new(
+                    cols as f32 * font_size.width.floor(),
+                    rows as f32 * font_size.height.floor(),
+                )
+            })
+            .fold(Vec2::ZERO, Vec2::max)
+    }
+
+    /// The area inside the rounded corners, where content may be drawn.
+    fn content_rect(&self, layout: &Response) -> Rect {
+        let rounding = self.rounding;
+        let radius = rounding
+            .nw
+            .max(rounding.ne)
+            .max(rounding.sw)
+            .max(rounding.se);
+        let radius = radius.clamp(0.0, layout.rect.size().min_elem() / 2.0);
+        // The rect touches the arc of each corner at 45 degrees.
+        layout
+            .rect
+            .shrink(radius * (1.0 - std::f32::consts::FRAC_1_SQRT_2))
+    }
+
+    /// The area occupied by the grid, which is the whole widget unless a
+    /// fixed size, a status line or rounded corners are set.
+    fn grid_rect(&self, layout: &Response) -> Rect {
+        let font_size = self.font.font_measure(&layout.ctx);
+        let mut available_rect = self.content_rect(layout);
+        if self.status_line.is_some() {
+            available_rect.max.y -= font_size.height.floor();
+        }
+
+        match self.fixed_size {
+            Some((cols, rows)) => Rect::from_center_size(
+                available_rect.center(),
+                Vec2::new(
+                    cols as f32 * font_size.width.floor(),
+                    rows as f32 * font_size.height.floor(),
+                ),
+            ),
+            None => available_rect,
+        }
+    }
+
+    /// Colors the cell at `point` was drawn with in the last frame, after
+    /// bold, dim, inverse, selection, highlights and the minimum contrast
+    /// are applied, e.g. to blend an overlay with the terminal. Returns
+    /// `None` for points outside the viewport.
+    pub fn resolved_colors_at(
+        &self,
+        point: TerminalGridPoint,
+    ) -> Option<(Color32, Color32)> {
+        let content = self.backend.last_content();
+        let row = point.line.0 + content.display_offset as i32;
+        let cells = content.viewport.get(usize::try_from(row).ok()?)?;
+        let cell = cells.get(point.column.0)?;
+        let is_trailing_blank =
+            cell.c == ' ' && point.column.0 >= trailing_start(cells);
+        Some(self.cell_colors(content, point, cell, is_trailing_blank))
+    }
+
+    fn cell_colors(
+        &self,
+        content: &RenderableContent,
+        point: TerminalGridPoint,
+        cell: &cell::Cell,
+        is_trailing_blank: bool,
+    ) -> (Color32, Color32) {
+        let flags = cell.flags;
+        let is_inverse = flags.contains(cell::Flags::INVERSE);
+        let is_dim = flags.intersects(cell::Flags::DIM | cell::Flags::DIM_BOLD);
+        let is_selected =
+            content.selectable_range.is_some_and(|r| r.contains(point));
+
+        let is_bright =
+            self.bold_is_bright && flags.contains(cell::Flags::BOLD) && !is_dim;
+        let mut fg = if is_bright {
+            self.theme.get_color(bright_color(cell.fg))
+        } else {
+            self.theme.get_color(cell.fg)
+        };
+        let mut bg = if is_trailing_blank && !self.draw_trailing_bg {
+            self.theme
+                .get_color(ansi::Color::Named(NamedColor::Background))
+        } else {
+            self.theme.get_color(cell.bg)
+        };
+        if is_dim {
+            fg = fg.linear_multiply(0.7);
+        }
+
+        match self.selection_colors.filter(|_| is_selected) {
+            Some((selection_fg, selection_bg)) => {
+                fg = selection_fg;
+                bg = selection_bg;
+            },
+            None if is_inverse || is_selected => {
+                std::mem::swap(&mut fg, &mut bg);
+            },
+            None => {},
+        }
+
+        if !is_selected {
+            if let Some((_, color)) = self
+                .highlights
+                .iter()
+                .rev()
+                .find(|(range, _)| range.contains(&point))
+            {
+                bg = *color;
+            }
+        }
+
+        fg = ensure_minimum_contrast(fg, bg, self.minimum_contrast);
+        (fg, bg)
+    }
+
     fn process_input(
-        self,
+        mut self,
         layout: &Response,
         state: &mut TerminalViewState,
     ) -> Self {
-        if !layout.has_focus() || !layout.contains_pointer() {
+        // A selection drag keeps receiving events when the pointer leaves
+        // the widget, so it can auto-scroll and finish outside of it.
+        let has_focus = layout.has_focus();
+        let can_scroll_unfocused =
+            !self.scroll_requires_focus && layout.contains_pointer();
+        if !(has_focus || can_scroll_unfocused)
+            || (!layout.contains_pointer() && !state.is_dragged)
+        {
+            if state.is_paste_requested {
+                self.process_requested_paste(layout, state);
+            }
             return self;
         }
 
+        // Pointer positions are shifted so the input handlers, which work
+        // relative to the layout, end up relative to the grid.
+        let grid_offset = self.grid_rect(layout).min - layout.rect.min;
         let modifiers = layout.ctx.input(|i| i.modifiers);
+        // Reports are input to the program, so read-only terminals select.
+        let mouse_reporting = self.mouse_reporting && !self.read_only;
         let events = layout.ctx.input(|i| i.events.clone());
+        // egui turns the platform copy and paste shortcuts into events, so
+        // their key presses must not copy or paste a second time.
+        let has_paste_event = events
+            .iter()
+            .any(|event| matches!(event, egui::Event::Paste(_)));
+        let has_copy_event = events
+            .iter()
+            .any(|event| matches!(event, egui::Event::Copy | egui::Event::Cut));
+        let screen_lines =
+            self.backend.last_content().terminal_size.screen_lines();
+        let page_lines = match self.scroll_page_lines {
+            Some(lines) => lines as i32,
+            None => screen_lines.saturating_sub(1).max(1) as i32,
+        };
+        // Bytes written within a frame are sent to the PTY at once, so
+        // typing and pasting do not lock and notify once per key.
+        let mut pending_write = Vec::new();
         for event in events {
+            if !has_focus && !matches!(event, egui::Event::MouseWheel { .. }) {
+                continue;
+            }
+
+            // Left to egui, which moves the focus.
+            let is_tab =
+                matches!(event, egui::Event::Key { key: Key::Tab, .. });
+            if is_tab && !self.capture_tab {
+                continue;
+            }
+
             let mut input_actions = vec![];
 
             match event {
+                egui::Event::Key {
+                    key: Key::C | Key::X,
+                    ..
+                } if has_copy_event => {},
                 egui::Event::Text(_)
                 | egui::Event::Key { .. }
                 | egui::Event::Copy
+                | egui::Event::Cut
                 | egui::Event::Paste(_) => {
+                    if let egui::Event::Paste(_) = event {
+                        state.is_paste_requested = false;
+                    }
+                    state.last_input_time = layout.ctx.input(|i| i.time);
                     input_actions.push(process_keyboard_event(
                         event,
                         self.backend,
                         &self.bindings_layout,
                         modifiers,
+                        has_paste_event,
+                        page_lines,
                     ))
                 },
-                egui::Event::MouseWheel { unit, delta, .. } => input_actions
-                    .push(process_mouse_wheel(
+                egui::Event::MouseWheel { unit, delta, .. } => {
+                    input_actions = process_mouse_wheel(
                         state,
+                        self.backend,
                         self.font.font_type().size,
                         unit,
                         delta,
-                    )),
+                        &modifiers,
+                        mouse_reporting,
+                    )
+                },
                 egui::Event::PointerButton {
                     button,
                     pressed,
@@ -180,156 +809,916 @@ impl<'a> TerminalView<'a> {
                     self.backend,
                     &self.bindings_layout,
                     button,
-                    pos,
+                    pos - grid_offset,
                     &modifiers,
                     pressed,
+                    mouse_reporting,
+                    self.multi_click_interval,
+                    self.multi_click_distance,
                 )),
                 egui::Event::PointerMoved(pos) => {
                     input_actions = process_mouse_move(
                         state,
                         layout,
                         self.backend,
-                        pos,
+                        pos - grid_offset,
                         &modifiers,
+                        mouse_reporting,
                     )
                 },
                 _ => {},
             };
 
-            for action in input_actions {
-                match action {
-                    InputAction::BackendCall(cmd) => {
-                        self.backend.process_command(cmd);
-                    },
-                    InputAction::WriteToClipboard(data) => {
-                        layout.ctx.output_mut(|o| o.copied_text = data);
-                    },
-                    InputAction::Ignore => {},
-                }
+            self.apply_input_actions(layout, input_actions, &mut pending_write);
+        }
+
+        if state.is_dragged {
+            let input_actions = process_selection_autoscroll(
+                layout,
+                self.backend,
+                self.grid_rect(layout),
+            );
+            self.apply_input_actions(layout, input_actions, &mut pending_write);
+        }
+        self.flush_pending_write(&mut pending_write);
+
+        self
+    }
+
+    /// Handles the paste requested from the context menu, which arrives on
+    /// a later frame when the pointer may be over the menu instead.
+    fn process_requested_paste(
+        &mut self,
+        layout: &Response,
+        state: &mut TerminalViewState,
+    ) {
+        let pasted = layout.ctx.input(|i| {
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            })
+        });
+
+        if let Some(text) = pasted {
+            state.is_paste_requested = false;
+            let mut pending_write = Vec::new();
+            self.apply_input_actions(
+                layout,
+                vec![InputAction::BackendCall(BackendCommand::Paste(text))],
+                &mut pending_write,
+            );
+            self.flush_pending_write(&mut pending_write);
+        }
+    }
+
+    fn show_context_menu(
+        mut self,
+        layout: &Response,
+        state: &mut TerminalViewState,
+    ) -> Self {
+        // Programs with mouse reporting get the right click instead.
+        let modifiers = layout.ctx.input(|i| i.modifiers);
+        if !self.context_menu
+            || is_mouse_reported(
+                self.mouse_reporting && !self.read_only,
+                self.backend.last_content().terminal_mode,
+                &modifiers,
+            )
+        {
+            return self;
+        }
+
+        let has_selection = self.backend.has_selection();
+        let read_only = self.read_only;
+        let mut menu_action = None;
+        layout.context_menu(|ui| {
+            if ui
+                .add_enabled(has_selection, egui::Button::new("Copy"))
+                .clicked()
+            {
+                menu_action = Some(BindingAction::Copy);
+            }
+            if ui
+                .add_enabled(!read_only, egui::Button::new("Paste"))
+                .clicked()
+            {
+                menu_action = Some(BindingAction::Paste);
+            }
+            if ui.button("Select All").clicked() {
+                menu_action = Some(BindingAction::SelectAll);
+            }
+            if ui
+                .add_enabled(has_selection, egui::Button::new("Clear"))
+                .clicked()
+            {
+                menu_action = Some(BindingAction::ClearSelection);
+            }
+            if menu_action.is_some() {
+                ui.close_menu();
+            }
+        });
+
+        let input_action = match menu_action {
+            Some(BindingAction::Copy) => {
+                InputAction::WriteToClipboard(self.backend.selectable_content())
+            },
+            Some(BindingAction::Paste) if self.clipboard.is_some() => {
+                InputAction::RequestPaste
+            },
+            Some(BindingAction::Paste) => {
+                // The clipboard is read by the integration, which sends a
+                // paste event on a later frame.
+                state.is_paste_requested = true;
+                layout
+                    .ctx
+                    .send_viewport_cmd(egui::ViewportCommand::RequestPaste);
+                InputAction::Ignore
+            },
+            Some(BindingAction::ClearSelection) => {
+                InputAction::BackendCall(BackendCommand::ClearSelection)
+            },
+            Some(BindingAction::SelectAll) => {
+                InputAction::BackendCall(BackendCommand::SelectAll)
+            },
+            _ => InputAction::Ignore,
+        };
+        let mut pending_write = Vec::new();
+        self.apply_input_actions(
+            layout,
+            vec![input_action],
+            &mut pending_write,
+        );
+
+        self
+    }
+
+    /// An I-beam over the text, an arrow while the program receives the
+    /// mouse and a pointing hand over a hovered link while a click would
+    /// open it.
+    fn update_cursor_icon(
+        self,
+        layout: &Response,
+        state: &TerminalViewState,
+    ) -> Self {
+        if !layout.hovered() {
+            return self;
+        }
+
+        let content = self.backend.last_content();
+        let modifiers = layout.ctx.input(|i| i.modifiers);
+        let is_link_hovered =
+            content.hovered_hyperlink.as_ref().is_some_and(|range| {
+                range.contains(&state.current_mouse_position_on_grid)
+            }) && self.bindings_layout.get_action(
+                InputKind::Mouse(PointerButton::Primary),
+                modifiers,
+                content.terminal_mode,
+            ) == BindingAction::LinkOpen;
+        let icon = if is_link_hovered {
+            CursorIcon::PointingHand
+        } else if is_mouse_reported(
+            self.mouse_reporting && !self.read_only,
+            content.terminal_mode,
+            &modifiers,
+        ) {
+            CursorIcon::Default
+        } else {
+            CursorIcon::Text
+        };
+        layout.ctx.set_cursor_icon(icon);
+
+        self
+    }
+
+    fn apply_input_actions(
+        &mut self,
+        layout: &Response,
+        input_actions: Vec<InputAction>,
+        pending_write: &mut Vec<u8>,
+    ) {
+        for action in input_actions {
+            match action {
+                InputAction::BackendCall(cmd) => {
+                    let terminal_mode =
+                        self.backend.last_content().terminal_mode;
+                    if self.read_only && is_pty_input(&cmd, terminal_mode) {
+                        continue;
+                    }
+
+                    match cmd {
+                        BackendCommand::Write(bytes) => {
+                            pending_write.extend(bytes);
+                        },
+                        cmd => {
+                            // Keep the order of writes and other commands.
+                            self.flush_pending_write(pending_write);
+                            self.backend.process_command(cmd);
+                        },
+                    }
+                },
+                InputAction::WriteToClipboard(data) => {
+                    match &mut self.clipboard {
+                        Some(clipboard) => clipboard.set_text(data),
+                        None => layout.ctx.output_mut(|o| o.copied_text = data),
+                    }
+                    // Copying leaves copy mode, like in tmux.
+                    if self
+                        .backend
+                        .last_content()
+                        .terminal_mode
+                        .contains(TermMode::VI)
+                    {
+                        self.backend
+                            .process_command(BackendCommand::ToggleCopyMode);
+                    }
+                },
+                InputAction::RequestPaste if self.read_only => {},
+                InputAction::RequestPaste => {
+                    match self.clipboard.as_mut().map(|c| c.get_text()) {
+                        Some(Some(text)) => {
+                            self.flush_pending_write(pending_write);
+                            self.backend
+                                .process_command(BackendCommand::Paste(text));
+                        },
+                        Some(None) => {},
+                        // Arrives as a paste event on a later frame.
+                        None => layout.ctx.send_viewport_cmd(
+                            egui::ViewportCommand::RequestPaste,
+                        ),
+                    }
+                },
+                InputAction::Ignore => {},
+            }
+        }
+    }
+
+    fn flush_pending_write(&mut self, pending_write: &mut Vec<u8>) {
+        if !pending_write.is_empty() {
+            self.backend.process_command(BackendCommand::Write(
+                std::mem::take(pending_write),
+            ));
+        }
+    }
+
+    fn show(
+        self,
+        state: &mut TerminalViewState,
+        layout: &Response,
+        painter: &Painter,
+    ) {
+        let grid_rect = self.grid_rect(layout);
+        let is_blink_phase_visible = self.cursor_blink_phase(state, layout);
+        let has_exited = self.backend.has_exited();
+        self.backend.sync();
+        let content = self.backend.last_content();
+        layout.widget_info(|| build_widget_info(content));
+        let layout_offset = grid_rect.min;
+        let global_bg = self
+            .theme
+            .get_color(ansi::Color::Named(NamedColor::Background));
+        // Also pads the remainder below the last row, which is less than a
+        // cell high.
+        match self.background_fill {
+            None => {
+                painter.rect_filled(layout.rect, self.rounding, global_bg);
+            },
+            Some(fill) => {
+                paint_background_fill(painter, layout.rect, self.rounding, fill)
+            },
+        }
+        let content_painter = painter.with_clip_rect(
+            painter.clip_rect().intersect(self.content_rect(layout)),
+        );
+
+        // Column where the trailing blanks start for every visible row.
+        let trailing_starts: Vec<usize> = content
+            .viewport
+            .iter()
+            .map(|row| trailing_start(row))
+            .collect();
+        let cursor_row =
+            content.cursor.point.line.0 + content.display_offset as i32;
+        let written_ends: Vec<usize> = content
+            .viewport
+            .iter()
+            .enumerate()
+            .map(|(row, cells)| {
+                written_end(
+                    cells,
+                    (row as i32 == cursor_row)
+                        .then_some(content.cursor.point.column.0),
+                )
+            })
+            .collect();
+
+        let cell_height = content.terminal_size.cell_height as f32;
+        let pixels_per_point = layout.ctx.pixels_per_point();
+        // Programs hide the cursor with DECTCEM (`CSI ? 25 l`). It belongs
+        // to the live screen, so it is not drawn while scrolled back, nor
+        // in copy mode, which draws its own.
+        let is_cursor_visible =
+            content.terminal_mode.contains(TermMode::SHOW_CURSOR)
+                && content.display_offset == 0
+                && content.copy_mode_cursor.is_none()
+                && is_blink_phase_visible;
+        let cell_count = content.terminal_size.columns()
+            * content.terminal_size.screen_lines();
+        let cell_width = content.terminal_size.cell_width as f32;
+        let glyph_width = self.font.font_measure(&layout.ctx).width;
+        let mut batch = ShapeBatch::new(
+            layout.ctx.clone(),
+            self.font.font_type(),
+            cell_width - glyph_width,
+            cell_count > MAX_UNBATCHED_CELLS,
+            self.crisp_text,
+        );
+
+        for indexed in content.display_iter() {
+            let flags = indexed.cell.flags;
+            let is_wide_char_spacer =
+                flags.contains(cell::Flags::WIDE_CHAR_SPACER);
+            if is_wide_char_spacer {
+                continue;
+            }
+
+            let is_cursor =
+                is_cursor_visible && content.cursor.point == indexed.point;
+            let is_wide_char = flags.contains(cell::Flags::WIDE_CHAR);
+            let is_hovered_hyperling =
+                content.hovered_hyperlink.as_ref().is_some_and(|r| {
+                    r.contains(&indexed.point)
+                        && r.contains(&state.current_mouse_position_on_grid)
+                });
+
+            let row =
+                (indexed.point.line.0 + content.display_offset as i32) as usize;
+            let is_trailing_blank = indexed.c == ' '
+                && trailing_starts
+                    .get(row)
+                    .is_some_and(|start| indexed.point.column.0 >= *start);
+
+            let (mut fg, bg) = self.cell_colors(
+                content,
+                indexed.point,
+                indexed.cell,
+                is_trailing_blank,
+            );
+
+            let mut cell_rect = content.cell_rect(indexed.point, layout_offset);
+            if is_wide_char {
+                cell_rect.set_width(cell_rect.width() * 2.0);
+            }
+            let cell_rect = snap_rect_to_pixels(cell_rect, pixels_per_point);
+
+            if self.background_fill.is_none() || bg != global_bg {
+                batch.push_background(cell_rect, bg);
+            }
+
+            // Handle hovered hyperlink underline
+            if is_hovered_hyperling {
+                batch.foregrounds.push(Shape::line_segment(
+                    [cell_rect.left_bottom(), cell_rect.right_bottom()],
+                    Stroke::new(cell_height * 0.15, fg),
+                ));
+            }
+
+            // Handle cursor rendering
+            if is_cursor {
+                let cursor_color = self.theme.get_color(content.cursor.fg);
+                batch.cursor.push(Shape::rect_filled(
+                    cell_rect,
+                    Rounding::default(),
+                    cursor_color,
+                ));
+                // The glyph under the block takes the cell background, so it
+                // stays readable on top of the cursor color.
+                fg = ensure_minimum_contrast(
+                    bg,
+                    cursor_color,
+                    self.minimum_contrast,
+                );
+            }
+
+            // An outline, so the selection under it stays visible.
+            if content.copy_mode_cursor == Some(indexed.point) {
+                batch.cursor.push(Shape::rect_stroke(
+                    cell_rect.shrink(0.5),
+                    Rounding::default(),
+                    Stroke::new(
+                        1.0,
+                        self.theme.get_color(ansi::Color::Named(
+                            NamedColor::Foreground,
+                        )),
+                    ),
+                ));
+            }
+
+            // Concealed text (SGR 8) keeps its background but no glyph.
+            if flags.contains(cell::Flags::HIDDEN) {
+                batch.push_glyph(indexed.point, cell_rect, ' ', fg);
+                continue;
+            }
+
+            // Draw text content
+            let (c, fg) = match indexed.c {
+                ' ' | '\t'
+                    if self.render_whitespace
+                        && written_ends.get(row).is_some_and(|end| {
+                            indexed.point.column.0 < *end
+                        }) =>
+                {
+                    let marker = if indexed.c == '\t' { '→' } else { '·' };
+                    (marker, fg.gamma_multiply(0.35))
+                },
+                ' ' | '\t' => {
+                    // Blanks keep a text run going, so it stays aligned.
+                    batch.push_glyph(indexed.point, cell_rect, ' ', fg);
+                    continue;
+                },
+                c => (c, fg),
+            };
+            if is_wide_char {
+                batch.push_wide_glyph(cell_rect, c, fg);
+            } else {
+                batch.push_glyph(indexed.point, cell_rect, c, fg);
+            }
+        }
+
+        batch.paint(&content_painter);
+
+        // A thin rule above every visible marked row.
+        let mark_color = self
+            .theme
+            .get_color(ansi::Color::Named(NamedColor::Foreground))
+            .gamma_multiply(0.35);
+        for line in &content.marks {
+            let row = line.0 + content.display_offset as i32;
+            if row < 0 || row >= content.terminal_size.screen_lines() as i32 {
+                continue;
+            }
+
+            let top = content
+                .cell_rect(
+                    TerminalGridPoint::new(*line, Column(0)),
+                    layout_offset,
+                )
+                .top();
+            let top = snap_to_pixels(top, pixels_per_point);
+            content_painter.hline(
+                grid_rect.x_range(),
+                top,
+                Stroke::new(1.0 / pixels_per_point, mark_color),
+            );
+        }
+
+        // `Line(0)` is the top of the live screen, its row in the viewport
+        // is the display offset.
+        if self.show_scroll_boundary && content.display_offset > 0 {
+            if content.display_offset < content.terminal_size.screen_lines() {
+                let top = content
+                    .cell_rect(
+                        TerminalGridPoint::new(Line(0), Column(0)),
+                        layout_offset,
+                    )
+                    .top();
+                content_painter.hline(
+                    grid_rect.x_range(),
+                    snap_to_pixels(top, pixels_per_point),
+                    Stroke::new(
+                        1.0 / pixels_per_point,
+                        self.theme
+                            .get_color(ansi::Color::Named(
+                                NamedColor::Foreground,
+                            ))
+                            .gamma_multiply(0.7),
+                    ),
+                );
+            } else {
+                let shadow = Rect::from_min_max(
+                    Pos2::new(
+                        grid_rect.left(),
+                        grid_rect.bottom() - cell_height,
+                    ),
+                    grid_rect.right_bottom(),
+                );
+                paint_background_fill(
+                    &content_painter,
+                    shadow,
+                    Rounding::ZERO,
+                    BackgroundFill::Gradient(
+                        Color32::TRANSPARENT,
+                        Color32::from_black_alpha(96),
+                    ),
+                );
+            }
+        }
+
+        if let Some(placeholder) = &self.startup_placeholder {
+            if !has_exited && !self.backend.has_output() {
+                content_painter.text(
+                    grid_rect.center(),
+                    Align2::CENTER_CENTER,
+                    placeholder,
+                    self.font.font_type(),
+                    self.theme
+                        .get_color(ansi::Color::Named(NamedColor::Foreground)),
+                );
+            }
+        }
+
+        if let Some(overlay) = &self.overlay {
+            overlay(&content_painter, &content.cell_geometry(layout_offset));
+        }
+
+        if self.backend.is_kept_open() {
+            let status_rect = Rect::from_min_max(
+                Pos2::new(grid_rect.left(), grid_rect.bottom() - cell_height),
+                grid_rect.right_bottom(),
+            );
+            content_painter.rect_filled(status_rect, Rounding::ZERO, global_bg);
+            content_painter.text(
+                status_rect.left_top(),
+                Align2::LEFT_TOP,
+                "[Process exited]",
+                self.font.font_type(),
+                self.theme
+                    .get_color(ansi::Color::Named(NamedColor::Foreground)),
+            );
+        }
+
+        if let Some(status_line) = &self.status_line {
+            let status_rect = Rect::from_min_size(
+                grid_rect.left_bottom(),
+                Vec2::new(grid_rect.width(), cell_height),
+            );
+            content_painter.rect_filled(status_rect, Rounding::ZERO, global_bg);
+            content_painter.text(
+                status_rect.left_top(),
+                Align2::LEFT_TOP,
+                status_line,
+                self.font.font_type(),
+                self.status_line_color.unwrap_or_else(|| {
+                    self.theme
+                        .get_color(ansi::Color::Named(NamedColor::Foreground))
+                }),
+            );
+        }
+
+        if let Some(tint) = self.inactive_tint {
+            if !layout.has_focus() {
+                painter.rect_filled(layout.rect, self.rounding, tint);
             }
         }
+    }
+}
 
-        self
+/// Collects the cell shapes in paint order layers. Contiguous glyphs of the
+/// same color on a line are laid out as one galley, and on large grids
+/// adjacent backgrounds of the same color are merged into one rect.
+struct ShapeBatch {
+    ctx: egui::Context,
+    font: FontId,
+    letter_spacing: f32,
+    coalesce_backgrounds: bool,
+    crisp_text: bool,
+    glyph_width: f32,
+    /// Whether a glyph advances by `glyph_width`, by character.
+    advances: HashMap<char, bool>,
+    backgrounds: Vec<Shape>,
+    cursor: Vec<Shape>,
+    foregrounds: Vec<Shape>,
+    background_run: Option<(Rect, Color32)>,
+    text_run: Option<TextRun>,
+}
+
+struct TextRun {
+    position: Pos2,
+    text: String,
+    color: Color32,
+    line: i32,
+    next_column: usize,
+}
+
+impl ShapeBatch {
+    fn new(
+        ctx: egui::Context,
+        font: FontId,
+        letter_spacing: f32,
+        coalesce_backgrounds: bool,
+        crisp_text: bool,
+    ) -> Self {
+        let glyph_width = ctx.fonts(|fonts| fonts.glyph_width(&font, 'M'));
+        Self {
+            ctx,
+            font,
+            letter_spacing,
+            coalesce_backgrounds,
+            crisp_text,
+            glyph_width,
+            advances: HashMap::new(),
+            backgrounds: Vec::new(),
+            cursor: Vec::new(),
+            foregrounds: Vec::new(),
+            background_run: None,
+            text_run: None,
+        }
     }
 
-    fn show(
-        self,
-        state: &mut TerminalViewState,
-        layout: &Response,
-        painter: &Painter,
-    ) {
-        let content = self.backend.sync();
-        let layout_offset = layout.rect.min;
-        let cell_height = content.terminal_size.cell_height as f32;
-        let cell_width = content.terminal_size.cell_width as f32;
+    fn push_background(&mut self, rect: Rect, color: Color32) {
+        if !self.coalesce_backgrounds {
+            self.backgrounds.push(Shape::rect_filled(
+                rect,
+                Rounding::ZERO,
+                color,
+            ));
+            return;
+        }
 
-        for indexed in content.grid.display_iter() {
-            let flags = indexed.cell.flags;
-            let is_wide_char_spacer =
-                flags.contains(cell::Flags::WIDE_CHAR_SPACER);
-            if is_wide_char_spacer {
-                continue;
+        if let Some((run, run_color)) = &mut self.background_run {
+            if *run_color == color
+                && run.max.x == rect.min.x
+                && run.y_range() == rect.y_range()
+            {
+                run.max.x = rect.max.x;
+                return;
             }
+        }
 
-            let is_app_cursor_mode =
-                content.terminal_mode.contains(TermMode::APP_CURSOR);
-            let is_wide_char = flags.contains(cell::Flags::WIDE_CHAR);
-            let is_inverse = flags.contains(cell::Flags::INVERSE);
-            let is_dim =
-                flags.intersects(cell::Flags::DIM | cell::Flags::DIM_BOLD);
-            let is_selected = content
-                .selectable_range
-                .map_or(false, |r| r.contains(indexed.point));
-            let is_hovered_hyperling =
-                content.hovered_hyperlink.as_ref().map_or(false, |r| {
-                    r.contains(&indexed.point)
-                        && r.contains(&state.current_mouse_position_on_grid)
-                });
+        self.flush_background();
+        self.background_run = Some((rect, color));
+    }
 
-            let x = layout_offset.x
-                + indexed.point.column.0.saturating_mul(cell_width as usize)
-                    as f32;
-            let y = layout_offset.y
-                + indexed
-                    .point
-                    .line
-                    .0
-                    .saturating_add(content.grid.display_offset() as i32)
-                    .saturating_mul(cell_height as i32)
-                    as f32;
-
-            let mut fg = self.theme.get_color(indexed.fg);
-            let mut bg = self.theme.get_color(indexed.bg);
-            let cell_width = if is_wide_char {
-                cell_width * 2.0
-            } else {
-                cell_width
-            };
+    /// Wide glyphs do not advance by one cell, so they are centered over
+    /// their two cells on their own.
+    fn push_wide_glyph(&mut self, cell_rect: Rect, c: char, color: Color32) {
+        let pixels_per_point = self.ctx.pixels_per_point();
+        let glyph = self.ctx.fonts(|fonts| {
+            Shape::text(
+                fonts,
+                Pos2 {
+                    x: snap_to_pixels(cell_rect.center().x, pixels_per_point),
+                    y: cell_rect.top(),
+                },
+                Align2::CENTER_TOP,
+                c,
+                self.font.clone(),
+                color,
+            )
+        });
+        self.flush_text();
+        self.foregrounds.push(glyph);
+    }
 
-            if is_dim {
-                fg = fg.linear_multiply(0.7);
+    fn push_glyph(
+        &mut self,
+        point: TerminalGridPoint,
+        cell_rect: Rect,
+        c: char,
+        color: Color32,
+    ) {
+        if !self.has_cell_advance(c) {
+            // A run would shift every following glyph off the grid, so the
+            // glyph is centered in its cell on its own.
+            self.flush_text();
+            let galley = self.ctx.fonts(|fonts| {
+                fonts.layout_no_wrap(c.to_string(), self.font.clone(), color)
+            });
+            let mut position = Pos2::new(
+                cell_rect.center().x - galley.size().x / 2.0,
+                cell_rect.top(),
+            );
+            if self.crisp_text {
+                let pixels_per_point = self.ctx.pixels_per_point();
+                position.x = snap_to_pixels(position.x, pixels_per_point);
+                position.y = snap_to_pixels(position.y, pixels_per_point);
             }
+            self.foregrounds
+                .push(Shape::galley(position, galley, color));
+            return;
+        }
 
-            if is_inverse || is_selected {
-                std::mem::swap(&mut fg, &mut bg);
+        if let Some(run) = &mut self.text_run {
+            if !self.crisp_text
+                && run.line == point.line.0
+                && run.next_column == point.column.0
+                && (run.color == color || c == ' ')
+            {
+                run.text.push(c);
+                run.next_column += 1;
+                return;
             }
+        }
 
-            painter.rect_filled(
-                Rect::from_min_size(
-                    Pos2::new(x, y),
-                    Vec2::new(cell_width, cell_height),
-                ),
+        self.flush_text();
+        if c != ' ' {
+            // Centers the glyph in its cell like a single glyph would be.
+            let mut position = cell_rect.left_top()
+                + Vec2::new(self.letter_spacing / 2.0, 0.0);
+            if self.crisp_text {
+                let pixels_per_point = self.ctx.pixels_per_point();
+                position.x = snap_to_pixels(position.x, pixels_per_point);
+                position.y = snap_to_pixels(position.y, pixels_per_point);
+            }
+            self.text_run = Some(TextRun {
+                position,
+                text: c.to_string(),
+                color,
+                line: point.line.0,
+                next_column: point.column.0 + 1,
+            });
+        }
+    }
+
+    /// Whether `c` advances by the width the cells are measured with.
+    /// Glyphs from fallback fonts, or other weights of the font, may not.
+    fn has_cell_advance(&mut self, c: char) -> bool {
+        // The primary monospace font covers ASCII.
+        if c.is_ascii() {
+            return true;
+        }
+
+        let glyph_width = self.glyph_width;
+        *self.advances.entry(c).or_insert_with(|| {
+            let width =
+                self.ctx.fonts(|fonts| fonts.glyph_width(&self.font, c));
+            (width - glyph_width).abs() < 0.01
+        })
+    }
+
+    fn flush_background(&mut self) {
+        if let Some((rect, color)) = self.background_run.take() {
+            self.backgrounds.push(Shape::rect_filled(
+                rect,
                 Rounding::ZERO,
-                bg,
+                color,
+            ));
+        }
+    }
+
+    fn flush_text(&mut self) {
+        let Some(mut run) = self.text_run.take() else {
+            return;
+        };
+
+        run.text.truncate(run.text.trim_end().len());
+        // Glyphs advance by the cell width instead of the font advance,
+        // so the run lines up with the grid.
+        let job = LayoutJob::single_section(
+            run.text,
+            TextFormat {
+                font_id: self.font.clone(),
+                color: run.color,
+                extra_letter_spacing: self.letter_spacing,
+                ..Default::default()
+            },
+        );
+        let galley = self.ctx.fonts(|fonts| fonts.layout_job(job));
+        self.foregrounds
+            .push(Shape::galley(run.position, galley, run.color));
+    }
+
+    fn paint(mut self, painter: &Painter) {
+        self.flush_background();
+        self.flush_text();
+        painter.extend(self.backgrounds);
+        painter.extend(self.cursor);
+        painter.extend(self.foregrounds);
+    }
+}
+
+fn paint_background_fill(
+    painter: &Painter,
+    rect: Rect,
+    rounding: Rounding,
+    fill: BackgroundFill,
+) {
+    match fill {
+        BackgroundFill::Solid(color) => {
+            painter.rect_filled(rect, rounding, color);
+        },
+        BackgroundFill::Gradient(top, bottom) => {
+            let mut mesh = Mesh::default();
+            mesh.colored_vertex(rect.left_top(), top);
+            mesh.colored_vertex(rect.right_top(), top);
+            mesh.colored_vertex(rect.left_bottom(), bottom);
+            mesh.colored_vertex(rect.right_bottom(), bottom);
+            mesh.add_triangle(0, 1, 2);
+            mesh.add_triangle(1, 2, 3);
+            painter.add(Shape::mesh(mesh));
+        },
+        BackgroundFill::Texture(texture_id) => {
+            painter.image(
+                texture_id,
+                rect,
+                Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                Color32::WHITE,
             );
+        },
+    }
+}
 
-            // Handle hovered hyperlink underline
-            if is_hovered_hyperling {
-                let underline_height = y + cell_height;
-                painter.line_segment(
-                    [
-                        Pos2::new(x, underline_height),
-                        Pos2::new(x + cell_width, underline_height),
-                    ],
-                    Stroke::new(cell_height * 0.15, fg),
-                );
-            }
+/// Commands that send data to the process running in the terminal. On the
+/// alternate screen with alternate scroll, scrolling sends arrow keys.
+fn is_pty_input(cmd: &BackendCommand, terminal_mode: TermMode) -> bool {
+    match cmd {
+        BackendCommand::Write(_)
+        | BackendCommand::WriteNoScroll(_)
+        | BackendCommand::Paste(_)
+        | BackendCommand::MouseReport(..) => true,
+        BackendCommand::Scroll(_) => terminal_mode
+            .contains(TermMode::ALT_SCREEN | TermMode::ALTERNATE_SCROLL),
+        _ => false,
+    }
+}
 
-            // Handle cursor rendering
-            if content.grid.cursor.point == indexed.point {
-                let cursor_color = self.theme.get_color(content.cursor.fg);
-                // let cell_width = if is_wide_char { cell_width * 2.0 } else { cell_width };
-                painter.rect_filled(
-                    Rect::from_min_size(
-                        Pos2::new(x, y),
-                        Vec2::new(cell_width, cell_height),
-                    ),
-                    Rounding::default(),
-                    cursor_color,
-                );
-            }
+/// Column where the trailing blanks of a row start.
+fn trailing_start(row: &[cell::Cell]) -> usize {
+    row.iter()
+        .rposition(|cell| cell.c != ' ')
+        .map_or(0, |column| column + 1)
+}
 
-            // Draw text content
-            if indexed.c != ' ' && indexed.c != '\t' {
-                if content.grid.cursor.point == indexed.point
-                    && is_app_cursor_mode
-                {
-                    std::mem::swap(&mut fg, &mut bg);
-                }
+/// Column after the last written cell of a row. Blanks before the cursor,
+/// blanks with colors or attributes and soft wrapped rows were written.
+fn written_end(row: &[cell::Cell], cursor_column: Option<usize>) -> usize {
+    if row
+        .last()
+        .is_some_and(|cell| cell.flags.contains(cell::Flags::WRAPLINE))
+    {
+        return row.len();
+    }
 
-                painter.text(
-                    Pos2 {
-                        x: x + (cell_width / 2.0),
-                        y,
-                    },
-                    Align2::CENTER_TOP,
-                    indexed.c,
-                    self.font.font_type(),
-                    fg,
-                );
-            }
+    let blank = cell::Cell::default();
+    let end = row
+        .iter()
+        .rposition(|cell| {
+            cell.c != ' ' || cell.bg != blank.bg || cell.flags != blank.flags
+        })
+        .map_or(0, |column| column + 1);
+    cursor_column.map_or(end, |column| end.max(column))
+}
+
+/// Maps the eight basic colors to their bright variants.
+fn bright_color(color: ansi::Color) -> ansi::Color {
+    match color {
+        ansi::Color::Named(named) if (named as usize) < 8 => {
+            ansi::Color::Named(named.to_bright())
+        },
+        ansi::Color::Indexed(index) if index < 8 => {
+            ansi::Color::Indexed(index + 8)
+        },
+        color => color,
+    }
+}
+
+/// Rounds a logical coordinate to the nearest physical pixel boundary.
+fn snap_to_pixels(value: f32, pixels_per_point: f32) -> f32 {
+    (value * pixels_per_point).round() / pixels_per_point
+}
+
+/// Snaps both corners of a rect to physical pixels, so adjacent cells share
+/// the same edge and backgrounds tile without seams on fractional scales.
+fn snap_rect_to_pixels(rect: Rect, pixels_per_point: f32) -> Rect {
+    Rect::from_min_max(
+        Pos2::new(
+            snap_to_pixels(rect.min.x, pixels_per_point),
+            snap_to_pixels(rect.min.y, pixels_per_point),
+        ),
+        Pos2::new(
+            snap_to_pixels(rect.max.x, pixels_per_point),
+            snap_to_pixels(rect.max.y, pixels_per_point),
+        ),
+    )
+}
+
+/// Exposes the visible grid text and the cursor position to the
+/// accessibility tree.
+fn build_widget_info(content: &RenderableContent) -> WidgetInfo {
+    let mut text = String::new();
+    let mut text_len = 0;
+    let mut cursor_index = 0;
+    let mut current_line = None;
+    for indexed in content.display_iter() {
+        if current_line.is_some_and(|line| line != indexed.point.line) {
+            text.push('\n');
+            text_len += 1;
+        }
+        current_line = Some(indexed.point.line);
+
+        if indexed.point == content.cursor.point {
+            cursor_index = text_len;
+        }
+
+        if !indexed.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+            text.push(indexed.c);
+            text_len += 1;
         }
     }
+
+    let mut info = WidgetInfo::labeled(WidgetType::TextEdit, true, "Terminal");
+    info.current_text_value = Some(text);
+    info.text_selection = Some(cursor_index..=cursor_index);
+    info
 }
 
 fn process_keyboard_event(
@@ -337,18 +1726,41 @@ fn process_keyboard_event(
     backend: &TerminalBackend,
     bindings_layout: &BindingsLayout,
     modifiers: Modifiers,
+    has_paste_event: bool,
+    page_lines: i32,
 ) -> InputAction {
     match event {
         egui::Event::Text(text) => {
             process_text_event(&text, modifiers, backend, bindings_layout)
         },
-        egui::Event::Paste(text) => InputAction::BackendCall(
-            BackendCommand::Write(text.as_bytes().to_vec()),
-        ),
-        egui::Event::Copy => {
-            let content = backend.selectable_content();
-            InputAction::WriteToClipboard(content)
+        egui::Event::Paste(text) => {
+            InputAction::BackendCall(BackendCommand::Paste(text))
+        },
+        // egui sends these for the copy and cut shortcuts of the platform,
+        // e.g. Ctrl+C or Ctrl+Insert, instead of their key presses. They
+        // copy the selection; without one, Ctrl+C and Ctrl+X reach the
+        // program.
+        egui::Event::Copy | egui::Event::Cut if backend.has_selection() => {
+            InputAction::WriteToClipboard(backend.selectable_content())
         },
+        egui::Event::Copy => process_keyboard_key(
+            backend,
+            bindings_layout,
+            Key::C,
+            modifiers,
+            true,
+            has_paste_event,
+            page_lines,
+        ),
+        egui::Event::Cut => process_keyboard_key(
+            backend,
+            bindings_layout,
+            Key::X,
+            modifiers,
+            true,
+            has_paste_event,
+            page_lines,
+        ),
         egui::Event::Key {
             key,
             pressed,
@@ -360,6 +1772,8 @@ fn process_keyboard_event(
             key,
             modifiers,
             pressed,
+            has_paste_event,
+            page_lines,
         ),
         _ => InputAction::Ignore,
     }
@@ -371,23 +1785,18 @@ fn process_text_event(
     backend: &TerminalBackend,
     bindings_layout: &BindingsLayout,
 ) -> InputAction {
-    if let Some(key) = Key::from_name(text) {
-        if bindings_layout.get_action(
-            InputKind::KeyCode(key),
-            modifiers,
-            backend.last_content().terminal_mode,
-        ) == BindingAction::Ignore
-        {
-            InputAction::BackendCall(BackendCommand::Write(
-                text.as_bytes().to_vec(),
-            ))
-        } else {
-            InputAction::Ignore
-        }
-    } else {
-        InputAction::BackendCall(BackendCommand::Write(
-            text.as_bytes().to_vec(),
-        ))
+    // Letters move the cursor in copy mode instead of typing.
+    if backend.last_content().terminal_mode.contains(TermMode::VI) {
+        return InputAction::Ignore;
+    }
+
+    match bindings_layout.text_bytes(
+        text,
+        modifiers,
+        backend.last_content().terminal_mode,
+    ) {
+        Some(bytes) => InputAction::BackendCall(BackendCommand::Write(bytes)),
+        None => InputAction::Ignore,
     }
 }
 
@@ -397,56 +1806,73 @@ fn process_keyboard_key(
     key: Key,
     modifiers: Modifiers,
     pressed: bool,
+    has_paste_event: bool,
+    page_lines: i32,
 ) -> InputAction {
     if !pressed {
         return InputAction::Ignore;
     }
 
-    let terminal_mode = backend.last_content().terminal_mode;
-    let binding_action = bindings_layout.get_action(
-        InputKind::KeyCode(key),
-        modifiers,
-        terminal_mode,
-    );
-
-    match binding_action {
-        BindingAction::Char(c) => {
-            let mut buf = [0, 0, 0, 0];
-            let str = c.encode_utf8(&mut buf);
-            InputAction::BackendCall(BackendCommand::Write(
-                str.as_bytes().to_vec(),
-            ))
+    match backend.key_action(bindings_layout, key, modifiers, page_lines) {
+        Some(KeyAction::Command(cmd)) => InputAction::BackendCall(cmd),
+        Some(KeyAction::Clipboard(ClipboardRequest::Copy(text))) => {
+            InputAction::WriteToClipboard(text)
+        },
+        Some(KeyAction::Clipboard(ClipboardRequest::Paste))
+            if !has_paste_event =>
+        {
+            InputAction::RequestPaste
         },
-        BindingAction::Esc(seq) => InputAction::BackendCall(
-            BackendCommand::Write(seq.as_bytes().to_vec()),
-        ),
         _ => InputAction::Ignore,
     }
 }
 
 fn process_mouse_wheel(
     state: &mut TerminalViewState,
+    backend: &TerminalBackend,
     font_size: f32,
     unit: MouseWheelUnit,
     delta: Vec2,
-) -> InputAction {
-    match unit {
+    modifiers: &Modifiers,
+    mouse_reporting: bool,
+) -> Vec<InputAction> {
+    let lines = match unit {
         MouseWheelUnit::Line => {
-            let lines = delta.y.signum() * delta.y.abs().ceil();
-            InputAction::BackendCall(BackendCommand::Scroll(lines as i32))
+            (delta.y.signum() * delta.y.abs().ceil()) as i32
         },
         MouseWheelUnit::Point => {
             state.scroll_pixels -= delta.y;
             let lines = (state.scroll_pixels / font_size).trunc();
             state.scroll_pixels %= font_size;
-            if lines != 0.0 {
-                InputAction::BackendCall(BackendCommand::Scroll(-lines as i32))
-            } else {
-                InputAction::Ignore
-            }
+            -lines as i32
         },
-        MouseWheelUnit::Page => InputAction::Ignore,
+        MouseWheelUnit::Page => 0,
+    };
+    if lines == 0 {
+        return vec![];
+    }
+
+    let terminal_mode = backend.last_content().terminal_mode;
+    if !is_mouse_reported(mouse_reporting, terminal_mode, modifiers) {
+        return vec![InputAction::BackendCall(BackendCommand::Scroll(lines))];
     }
+
+    // Programs with mouse reporting get one wheel report per line.
+    let button = if lines > 0 {
+        MouseButton::ScrollUp
+    } else {
+        MouseButton::ScrollDown
+    };
+    (0..lines.unsigned_abs())
+        .map(|_| {
+            InputAction::BackendCall(BackendCommand::MouseReport(
+                button.clone(),
+                *modifiers,
+                state.current_mouse_position_on_grid,
+                true,
+            ))
+        })
+        .collect()
 }
 
 fn process_button_click(
@@ -458,6 +1884,9 @@ fn process_button_click(
     position: Pos2,
     modifiers: &Modifiers,
     pressed: bool,
+    mouse_reporting: bool,
+    multi_click_interval: Duration,
+    multi_click_distance: f32,
 ) -> InputAction {
     match button {
         PointerButton::Primary => process_left_button(
@@ -468,7 +1897,29 @@ fn process_button_click(
             position,
             modifiers,
             pressed,
+            mouse_reporting,
+            multi_click_interval,
+            multi_click_distance,
         ),
+        PointerButton::Secondary | PointerButton::Middle
+            if is_mouse_reported(
+                mouse_reporting,
+                backend.last_content().terminal_mode,
+                modifiers,
+            ) =>
+        {
+            let button = if button == PointerButton::Secondary {
+                MouseButton::RightButton
+            } else {
+                MouseButton::MiddleButton
+            };
+            InputAction::BackendCall(BackendCommand::MouseReport(
+                button,
+                *modifiers,
+                state.current_mouse_position_on_grid,
+                pressed,
+            ))
+        },
         _ => InputAction::Ignore,
     }
 }
@@ -481,9 +1932,12 @@ fn process_left_button(
     position: Pos2,
     modifiers: &Modifiers,
     pressed: bool,
+    mouse_reporting: bool,
+    multi_click_interval: Duration,
+    multi_click_distance: f32,
 ) -> InputAction {
     let terminal_mode = backend.last_content().terminal_mode;
-    if terminal_mode.intersects(TermMode::MOUSE_MODE) {
+    if is_mouse_reported(mouse_reporting, terminal_mode, modifiers) {
         InputAction::BackendCall(BackendCommand::MouseReport(
             MouseButton::LeftButton,
             *modifiers,
@@ -491,75 +1945,121 @@ fn process_left_button(
             pressed,
         ))
     } else if pressed {
-        process_left_button_pressed(state, layout, position)
-    } else {
-        process_left_button_released(
+        process_left_button_pressed(
             state,
             layout,
-            backend,
-            bindings_layout,
             position,
-            modifiers,
+            multi_click_interval,
+            multi_click_distance,
         )
+    } else {
+        process_left_button_released(state, backend, bindings_layout, modifiers)
     }
 }
 
+/// Whether mouse input goes to the program instead of selecting text.
+/// Holding Shift bypasses reporting, like in most terminals.
+fn is_mouse_reported(
+    mouse_reporting: bool,
+    terminal_mode: TermMode,
+    modifiers: &Modifiers,
+) -> bool {
+    mouse_reporting
+        && terminal_mode.intersects(TermMode::MOUSE_MODE)
+        && !modifiers.shift
+}
+
 fn process_left_button_pressed(
     state: &mut TerminalViewState,
     layout: &Response,
     position: Pos2,
+    multi_click_interval: Duration,
+    multi_click_distance: f32,
 ) -> InputAction {
+    let time = layout.ctx.input(|i| i.time);
+    let is_repeated_click = time - state.last_click_time
+        <= multi_click_interval.as_secs_f64()
+        && position.distance(state.last_click_position) <= multi_click_distance;
+    state.click_count = if is_repeated_click {
+        state.click_count % 3 + 1
+    } else {
+        1
+    };
+    state.last_click_time = time;
+    state.last_click_position = position;
     state.is_dragged = true;
-    InputAction::BackendCall(build_start_select_command(layout, position))
+
+    // The granularity is kept by the selection itself, so dragging after a
+    // double or triple click extends it by words or lines.
+    let selection_type = match state.click_count {
+        2 => SelectionType::Semantic,
+        3 => SelectionType::Lines,
+        _ => SelectionType::Simple,
+    };
+
+    InputAction::BackendCall(BackendCommand::SelectStart(
+        selection_type,
+        position.x - layout.rect.min.x,
+        position.y - layout.rect.min.y,
+    ))
 }
 
 fn process_left_button_released(
     state: &mut TerminalViewState,
-    layout: &Response,
     backend: &TerminalBackend,
     bindings_layout: &BindingsLayout,
-    position: Pos2,
     modifiers: &Modifiers,
 ) -> InputAction {
     state.is_dragged = false;
-    if layout.double_clicked() || layout.triple_clicked() {
-        InputAction::BackendCall(build_start_select_command(layout, position))
-    } else {
-        let terminal_content = backend.last_content();
-        let binding_action = bindings_layout.get_action(
-            InputKind::Mouse(PointerButton::Primary),
-            *modifiers,
-            terminal_content.terminal_mode,
-        );
+    let terminal_content = backend.last_content();
+    let binding_action = bindings_layout.get_action(
+        InputKind::Mouse(PointerButton::Primary),
+        *modifiers,
+        terminal_content.terminal_mode,
+    );
 
-        if binding_action == BindingAction::LinkOpen {
-            InputAction::BackendCall(BackendCommand::ProcessLink(
-                LinkAction::Open,
-                state.current_mouse_position_on_grid,
-            ))
-        } else {
-            InputAction::Ignore
-        }
+    if binding_action == BindingAction::LinkOpen {
+        InputAction::BackendCall(BackendCommand::ProcessLink(
+            LinkAction::Open,
+            state.current_mouse_position_on_grid,
+        ))
+    } else {
+        InputAction::Ignore
     }
 }
 
-fn build_start_select_command(
+/// Scrolls the viewport while a selection is dragged above or below the
+/// grid, proportionally to the distance, and extends the selection to the
+/// edge row. Keeps repainting until the pointer is back or released.
+fn process_selection_autoscroll(
     layout: &Response,
-    cursor_position: Pos2,
-) -> BackendCommand {
-    let selection_type = if layout.double_clicked() {
-        SelectionType::Semantic
-    } else if layout.triple_clicked() {
-        SelectionType::Lines
+    backend: &TerminalBackend,
+    grid_rect: Rect,
+) -> Vec<InputAction> {
+    let Some(position) = layout.ctx.pointer_latest_pos() else {
+        return vec![];
+    };
+
+    let cell_height =
+        backend.last_content().terminal_size.cell_height.max(1) as f32;
+    let lines = if position.y < grid_rect.top() {
+        ((grid_rect.top() - position.y) / cell_height).ceil() as i32
+    } else if position.y > grid_rect.bottom() {
+        -((position.y - grid_rect.bottom()) / cell_height).ceil() as i32
     } else {
-        SelectionType::Simple
+        return vec![];
     };
 
-    BackendCommand::SelectStart(
-        selection_type,
-        cursor_position.x - layout.rect.min.x,
-        cursor_position.y - layout.rect.min.y,
-    )
+    layout.ctx.request_repaint();
+    let x = position.x.clamp(grid_rect.left(), grid_rect.right());
+    let y = position.y.clamp(grid_rect.top(), grid_rect.bottom() - 1.0);
+    vec![
+        InputAction::BackendCall(BackendCommand::Scroll(lines)),
+        InputAction::BackendCall(BackendCommand::SelectUpdate(
+            x - grid_rect.left(),
+            y - grid_rect.top(),
+        )),
+    ]
 }
 
 fn process_mouse_move(
@@ -568,6 +2068,7 @@ fn process_mouse_move(
     backend: &TerminalBackend,
     position: Pos2,
     modifiers: &Modifiers,
+    mouse_reporting: bool,
 ) -> Vec<InputAction> {
     let terminal_content = backend.last_content();
     let cursor_x = position.x - layout.rect.min.x;
@@ -576,14 +2077,15 @@ fn process_mouse_move(
         cursor_x,
         cursor_y,
         &terminal_content.terminal_size,
-        terminal_content.grid.display_offset(),
+        terminal_content.display_offset,
     );
 
     let mut actions = vec![];
     // Handle command or selection update based on terminal mode and modifiers
     if state.is_dragged {
         let terminal_mode = terminal_content.terminal_mode;
-        let cmd = if terminal_mode.contains(TermMode::MOUSE_MOTION)
+        let cmd = if mouse_reporting
+            && terminal_mode.contains(TermMode::MOUSE_MOTION)
             && modifiers.is_none()
         {
             InputAction::BackendCall(BackendCommand::MouseReport(
@@ -611,3 +2113,183 @@ fn process_mouse_move(
 
     actions
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{is_pty_input, written_end, ShapeBatch};
+    #[cfg(not(feature = "tokio"))]
+    use super::{process_keyboard_event, InputAction};
+    #[cfg(not(feature = "tokio"))]
+    use super::{TerminalView, TerminalViewState};
+    use crate::BackendCommand;
+    #[cfg(not(feature = "tokio"))]
+    use crate::BindingsLayout;
+    #[cfg(not(feature = "tokio"))]
+    use crate::TerminalBackend;
+    use alacritty_terminal::index::{Column, Line, Point};
+    use alacritty_terminal::term::cell::{Cell, Flags};
+    use alacritty_terminal::term::TermMode;
+    #[cfg(not(feature = "tokio"))]
+    use egui::Modifiers;
+    use egui::{Color32, FontId, Pos2, Rect, Shape, Vec2};
+
+    #[test]
+    fn glyphs_of_other_widths_keep_the_grid() {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            let font = FontId::monospace(14.0);
+            let cell_width = ctx.fonts(|f| f.glyph_width(&font, 'M')).ceil();
+            let glyph_width = ctx.fonts(|f| f.glyph_width(&font, 'M'));
+            let letter_spacing = cell_width - glyph_width;
+            let mut batch = ShapeBatch::new(
+                ctx.clone(),
+                font,
+                letter_spacing,
+                false,
+                false,
+            );
+            let cell_rect = |column: usize| {
+                Rect::from_min_size(
+                    Pos2::new(column as f32 * cell_width, 0.0),
+                    Vec2::new(cell_width, 16.0),
+                )
+            };
+            // The emoji comes from a fallback font with another advance.
+            let emoji_rect = cell_rect(1).union(cell_rect(2));
+            batch.push_glyph(
+                Point::new(Line(0), Column(0)),
+                cell_rect(0),
+                'a',
+                Color32::WHITE,
+            );
+            batch.push_wide_glyph(emoji_rect, '😀', Color32::WHITE);
+            batch.push_glyph(
+                Point::new(Line(0), Column(3)),
+                cell_rect(3),
+                'b',
+                Color32::WHITE,
+            );
+            batch.flush_text();
+
+            let glyphs: Vec<Rect> = batch
+                .foregrounds
+                .iter()
+                .map(|shape| match shape {
+                    Shape::Text(text) => {
+                        Rect::from_min_size(text.pos, text.galley.size())
+                    },
+                    _ => panic!("unexpected shape"),
+                })
+                .collect();
+            assert_eq!(glyphs.len(), 3);
+            assert!(
+                (glyphs[1].center().x - emoji_rect.center().x).abs() < 0.01
+            );
+            assert_eq!(
+                glyphs[2].min.x,
+                cell_rect(3).min.x + letter_spacing / 2.0
+            );
+        });
+    }
+
+    #[test]
+    fn trailing_spaces_before_the_cursor_are_written() {
+        let mut row = vec![Cell::default(); 8];
+        row[0].c = 'l';
+        row[1].c = 's';
+        assert_eq!(written_end(&row, None), 2);
+        assert_eq!(written_end(&row, Some(5)), 5);
+
+        row[7].flags.insert(Flags::WRAPLINE);
+        assert_eq!(written_end(&row, None), 8);
+    }
+
+    #[test]
+    #[cfg(not(feature = "tokio"))]
+    fn copy_event_copies_the_selection() {
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        let mut backend =
+            TerminalBackend::new_replay(0, egui::Context::default(), sender)
+                .unwrap();
+        backend.feed_bytes(b"hello");
+        backend.process_command(BackendCommand::SelectAll);
+
+        let action = process_keyboard_event(
+            egui::Event::Copy,
+            &backend,
+            &BindingsLayout::default(),
+            Modifiers::CTRL,
+            false,
+            1,
+        );
+        assert!(matches!(
+            action,
+            InputAction::WriteToClipboard(text) if text.starts_with("hello")
+        ));
+    }
+
+    #[test]
+    fn read_only_scroll_reaches_pty_only_with_alternate_scroll() {
+        let scroll = BackendCommand::Scroll(3);
+        assert!(!is_pty_input(&scroll, TermMode::default()));
+        assert!(!is_pty_input(&scroll, TermMode::ALT_SCREEN));
+        assert!(is_pty_input(
+            &scroll,
+            TermMode::ALT_SCREEN | TermMode::ALTERNATE_SCROLL
+        ));
+        assert!(!is_pty_input(
+            &BackendCommand::ClearSelection,
+            TermMode::ALT_SCREEN | TermMode::ALTERNATE_SCROLL
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "tokio"))]
+    fn pointer_maps_to_the_letterboxed_grid() {
+        let ctx = egui::Context::default();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        let mut backend =
+            TerminalBackend::new_replay(0, ctx.clone(), sender).unwrap();
+        // Returns the id and rect of the widget.
+        let run = |backend: &mut TerminalBackend, events: Vec<egui::Event>| {
+            let mut widget = (egui::Id::NULL, Rect::NOTHING);
+            let input = egui::RawInput {
+                screen_rect: Some(Rect::from_min_size(
+                    Pos2::ZERO,
+                    Vec2::new(800.0, 600.0),
+                )),
+                events,
+                ..Default::default()
+            };
+            let _ = ctx.run(input, |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    let view = TerminalView::new(ui, backend)
+                        .set_focus(true)
+                        .set_fixed_size(10, 5);
+                    widget.0 = view.widget_id;
+                    widget.1 = ui.add(view).rect;
+                });
+            });
+            widget
+        };
+
+        let (_, widget_rect) = run(&mut backend, Vec::new());
+        let terminal_size = backend.last_content().terminal_size;
+        let cell_size = Vec2::new(
+            terminal_size.cell_width as f32,
+            terminal_size.cell_height as f32,
+        );
+        let grid_min = widget_rect.center() - cell_size * Vec2::new(5.0, 2.5);
+        let pos = grid_min + cell_size * Vec2::new(2.5, 1.5);
+        let (widget_id, _) =
+            run(&mut backend, vec![egui::Event::PointerMoved(pos)]);
+
+        let state = ctx
+            .memory(|m| m.data.get_temp::<TerminalViewState>(widget_id))
+            .unwrap();
+        assert_eq!(
+            state.current_mouse_position_on_grid,
+            Point::new(Line(1), Column(2))
+        );
+    }
+}